@@ -0,0 +1,41 @@
+// prepended with `gpu_culling::shader_snippet(1)`, which provides `culled_instance`
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+}
+
+@vertex
+fn vs_main(vertex: VertexInput, @builtin(instance_index) instance_index: u32) -> VertexOutput {
+    let instance = culled_instance(instance_index);
+
+    // cubes are tinted by where they stand, walls stay grey
+    let world_position = instance.model * vec4<f32>(vertex.position, 1.0);
+    var tint = vec3<f32>(0.6);
+    if instance.batch == 0u {
+        tint = 0.5 + 0.5 * sin(world_position.xz.xyy * vec3<f32>(0.05, 0.07, 0.03) + vec3<f32>(0.0, 2.0, 4.0));
+    }
+
+    var out: VertexOutput;
+    out.clip_position = camera.view_proj * world_position;
+    out.color = vertex.color * tint;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 1.0);
+}
@@ -0,0 +1,348 @@
+use wgpu::util::DeviceExt;
+use wgpu_renderer::camera::{self, CameraController, CameraUniform};
+use wgpu_renderer::engine::*;
+use wgpu_renderer::gpu_culling::{self, CullBatch, CullInstance, GpuCullingPass};
+use wgpu_renderer::object::{self, Vertex};
+use wgpu_renderer::pipeline::{self, PipelineObject};
+use wgpu_renderer::scene::*;
+use wgpu_renderer::texture;
+use winit::event::{ElementState, KeyEvent, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+// cubes per side of the grid, and the distance between them
+const GRID_SIZE: i32 = 160;
+const GRID_SPACING: f32 = 2.5;
+// walls across the grid, tall enough to hide the cubes behind them
+const WALL_COUNT: i32 = 6;
+const CUBE_BATCH: u32 = 0;
+const WALL_BATCH: u32 = 1;
+const CAMERA_BIND_GROUP: u32 = 0;
+
+// unit cube around the origin, faces darker the further they point down
+fn create_cube(device: &wgpu::Device) -> object::Mesh {
+    let x = cgmath::Vector3::unit_x();
+    let y = cgmath::Vector3::unit_y();
+    let z = cgmath::Vector3::unit_z();
+    // normal and two edges with `u.cross(v) == normal`, so the corners wind counter clockwise
+    let faces = [
+        (x, y, z, 0.8),
+        (-x, z, y, 0.8),
+        (y, z, x, 1.0),
+        (-y, x, z, 0.4),
+        (z, x, y, 0.65),
+        (-z, y, x, 0.65),
+    ];
+
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+    for (normal, u, v, shade) in faces {
+        let first = vertices.len() as u16;
+        for (s, t) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            vertices.push(Vertex {
+                position: ((normal + u * s + v * t) * 0.5).into(),
+                color: [shade; 3],
+                tex_coords: [(s + 1.0) * 0.5, (t + 1.0) * 0.5],
+            });
+        }
+        indices.extend([0, 1, 2, 0, 2, 3].map(|i| first + i));
+    }
+
+    object::Mesh::create_mesh(device, vertices, indices)
+}
+
+// a grid of small cubes bobbing at different heights, and walls standing across it
+fn create_instances(mesh: &object::Mesh) -> Vec<CullInstance> {
+    let half = GRID_SIZE as f32 * GRID_SPACING * 0.5;
+    let mut instances = Vec::with_capacity((GRID_SIZE * GRID_SIZE + WALL_COUNT) as usize);
+
+    for row in 0..GRID_SIZE {
+        for column in 0..GRID_SIZE {
+            let x = column as f32 * GRID_SPACING - half;
+            let z = row as f32 * GRID_SPACING - half;
+            let height = 0.5 + 0.5 * (x * 0.1).sin() * (z * 0.13).cos();
+            let model = cgmath::Matrix4::from_translation(cgmath::Vector3::new(x, height, z))
+                * cgmath::Matrix4::from_angle_y(cgmath::Deg(x * 7.0 + z * 3.0));
+            instances.push(CullInstance::new(model, &mesh.bounds, CUBE_BATCH));
+        }
+    }
+
+    for i in 0..WALL_COUNT {
+        let z = (i as f32 + 0.5) / WALL_COUNT as f32 * 2.0 * half - half;
+        let model = cgmath::Matrix4::from_translation(cgmath::Vector3::new(0.0, 6.0, z))
+            * cgmath::Matrix4::from_nonuniform_scale(half * 1.6, 12.0, 1.0);
+        instances.push(CullInstance::new(model, &mesh.bounds, WALL_BATCH));
+    }
+
+    instances
+}
+
+pub fn input(engine: &mut Engine, event: &WindowEvent) -> bool {
+    match event {
+        WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    physical_key: PhysicalKey::Code(KeyCode::KeyH),
+                    state: ElementState::Pressed,
+                    ..
+                },
+            ..
+        } => {
+            if let Some(gpu_culling_pass) = engine.renderer.gpu_culling_pass.as_mut() {
+                gpu_culling_pass.hi_z = !gpu_culling_pass.hi_z;
+                println!(
+                    "occlusion culling {}",
+                    if gpu_culling_pass.hi_z { "on" } else { "off" }
+                );
+            }
+            true
+        }
+        _ => camera::input(engine, event),
+    }
+}
+
+pub fn init(engine: &mut Engine) {
+    let scene = &mut engine.scene;
+    let device = &engine.device;
+    let config = &engine.config;
+
+    let cube = create_cube(device);
+    let instances = create_instances(&cube);
+    // walls use the cube too, with multi draw indirect both batches go out in one call
+    let batches = [
+        CullBatch::whole_mesh(0, &cube),
+        CullBatch::whole_mesh(0, &cube),
+    ];
+    let mut gpu_culling_pass = GpuCullingPass::new(device, &instances, &batches);
+    scene
+        .objects
+        .push(object::get_object_from_mesh(cube, [0.0; 3]));
+
+    let camera = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+    });
+
+    let camera_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+    let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &camera_bind_group_layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: camera.as_entire_binding(),
+        }],
+    });
+
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: wgpu::ShaderSource::Wgsl(
+            (gpu_culling::shader_snippet(1) + include_str!("culling.wgsl")).into(),
+        ),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Pipeline Layout"),
+        bind_group_layouts: &[
+            &camera_bind_group_layout,
+            &gpu_culling_pass.instance_bind_group_layout,
+        ],
+        push_constant_ranges: &[],
+    });
+
+    let create_pipeline = |polygon_mode| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                unclipped_depth: false,
+                polygon_mode,
+                conservative: false,
+            },
+            depth_stencil: pipeline::create_depth_stencil_state(),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    };
+
+    let mut pipelines = vec![
+        PipelineObject {
+            pipeline: create_pipeline(wgpu::PolygonMode::Fill),
+            polygon_mode: wgpu::PolygonMode::Fill,
+        },
+        PipelineObject {
+            pipeline: create_pipeline(wgpu::PolygonMode::Line),
+            polygon_mode: wgpu::PolygonMode::Line,
+        },
+    ];
+    scene.update_pipelines(&mut pipelines);
+
+    // the engine keeps the pass in step with the depth texture from here on
+    let depth = texture::Texture::create_depth_texture(device, config, None);
+    gpu_culling_pass.hi_z = true;
+    gpu_culling_pass.resize(device, &depth);
+
+    let renderer = &mut engine.renderer;
+    renderer
+        .bind_group_manager
+        .add_bind_group(CAMERA_BIND_GROUP, camera_bind_group);
+    renderer.buffer_manager.add_buffer(0, camera);
+    renderer.depth_texture = Some(depth);
+    renderer.gpu_culling_pass = Some(gpu_culling_pass);
+}
+
+pub fn render(engine: &Engine) -> Result<(), wgpu::SurfaceError> {
+    let surface = &engine.surface;
+    let device = &engine.device;
+    let queue = &engine.queue;
+    let renderer = &engine.renderer;
+    let gpu_culling_pass = renderer.gpu_culling_pass.as_ref().unwrap();
+
+    let output = surface.get_current_texture()?;
+    let view = output
+        .texture
+        .create_view(&wgpu::TextureViewDescriptor::default());
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Render Encoder"),
+    });
+
+    gpu_culling_pass.cull(&mut encoder, queue, &engine.scene.camera_uniform);
+
+    let depth_stencil_attachment =
+        renderer
+            .depth_texture
+            .as_ref()
+            .map(|texture| wgpu::RenderPassDepthStencilAttachment {
+                view: &texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            });
+
+    {
+        let mut _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(renderer.state.clear_color),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        if renderer.state.polygon_fill {
+            _render_pass.set_pipeline(renderer.pipeline_manager.first_polygon_fill_item());
+        } else {
+            _render_pass.set_pipeline(renderer.pipeline_manager.first_polygon_line_item());
+        }
+        _render_pass.set_bind_group(
+            0,
+            &renderer
+                .bind_group_manager
+                .find_by_id(CAMERA_BIND_GROUP)
+                .unwrap()
+                .bind_group,
+            &[],
+        );
+        gpu_culling_pass.draw(&mut _render_pass, 1, &[&engine.scene.objects[0].mesh]);
+    }
+
+    // the depth just drawn is what the next frame's occlusion test uses
+    gpu_culling_pass.build_hi_z(&mut encoder);
+
+    queue.submit(std::iter::once(encoder.finish()));
+    output.present();
+
+    Ok(())
+}
+
+fn update(engine: &mut Engine) {
+    let renderer = &mut engine.renderer;
+    let scene = &mut engine.scene;
+    let queue = &engine.queue;
+
+    let dt = engine.last_render_time.elapsed();
+    engine.last_render_time = instant::Instant::now();
+
+    scene.camera_controller.update_camera(&mut scene.camera, dt);
+    scene
+        .camera_uniform
+        .update_view_proj(&scene.camera, &scene.projection);
+
+    queue.write_buffer(
+        &renderer.buffer_manager.find_by_id(0).unwrap().buffer,
+        0,
+        bytemuck::cast_slice(&[scene.camera_uniform]),
+    );
+}
+
+fn main() {
+    let scene = Scene {
+        camera: camera::Camera::new(
+            [0.0, 2.0, -210.0],
+            cgmath::Rad(camera::SAFE_FRAC_PI_2),
+            cgmath::Rad(-0.05),
+        ),
+        camera_controller: CameraController::new(20.0, 1.0),
+        camera_uniform: Default::default(),
+        projection: camera::Projection::new(1280, 720, cgmath::Deg(60.0), 0.1, 500.0),
+        main_view: Default::default(),
+        cameras: vec![],
+        camera_path: Default::default(),
+        objects: vec![],
+        lights: vec![],
+        pipeline_objects: vec![],
+    };
+
+    let commands = CommandBundle {
+        input_command: Box::new(input),
+        init_command: Box::new(init),
+        render_command: Box::new(render),
+        update_command: Box::new(update),
+    };
+
+    pollster::block_on(run(Some(scene), commands));
+}
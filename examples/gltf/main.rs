@@ -3,11 +3,17 @@ use std::vec;
 use bytemuck::{Pod, Zeroable};
 use cgmath::SquareMatrix;
 use wgpu::util::DeviceExt;
-use wgpu_renderer::camera::{self, CameraUniform};
+use wgpu_renderer::camera::{self, CameraController, CameraUniform, FlyController};
+use wgpu_renderer::camera_path::{CameraPath, CameraPathPlayer};
+use wgpu_renderer::culling::Aabb;
 use wgpu_renderer::engine::*;
+use wgpu_renderer::input_map::InputMap;
+use wgpu_renderer::orbit::{ArcballController, OrbitController};
 use wgpu_renderer::pipeline::PipelineObject;
 use wgpu_renderer::scene::*;
 use wgpu_renderer::{object, pipeline, texture};
+use winit::event::{ElementState, KeyEvent, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Pod, Zeroable)]
@@ -23,7 +29,98 @@ impl RotationUniform {
     }
 }
 
+// written by `K`, played back by `L`
+const CAMERA_PATH: &str = "camera_path.txt";
+
+// world space bounds of every object in the scene
+fn scene_bounds(scene: &Scene) -> (cgmath::Point3<f32>, cgmath::Point3<f32>) {
+    let bounds = scene
+        .objects
+        .iter()
+        .map(|object| object.world_bounds())
+        .reduce(Aabb::union)
+        .expect("the scene has the fox in it");
+    (bounds.min, bounds.max)
+}
+
+pub fn input(engine: &mut Engine, event: &WindowEvent) -> bool {
+    let WindowEvent::KeyboardInput {
+        event:
+            KeyEvent {
+                physical_key: PhysicalKey::Code(key),
+                state: ElementState::Pressed,
+                ..
+            },
+        ..
+    } = event
+    else {
+        return camera::input(engine, event);
+    };
+
+    let scene = &mut engine.scene;
+    match key {
+        // cycles fly -> orbit -> arcball, the new controller takes over from the current view
+        KeyCode::KeyV => {
+            let target = cgmath::Point3::new(0.0, 1.0, 0.0);
+            scene.camera_controller = match scene.camera_controller {
+                CameraController::Fly(_) => {
+                    CameraController::Orbit(OrbitController::from_camera(&scene.camera, target))
+                }
+                CameraController::Orbit(_) => {
+                    CameraController::Arcball(ArcballController::from_camera(&scene.camera, target))
+                }
+                CameraController::Arcball(_) => {
+                    let mut fly = FlyController::default();
+                    fly.scroll_adjusts_speed = true;
+                    fly.translation_smoothing = 0.1;
+                    CameraController::Fly(fly)
+                }
+            };
+            true
+        }
+        // records the current view 2 seconds after the last keyframe and saves the path
+        KeyCode::KeyK => {
+            let path = &mut scene.camera_path.path;
+            path.record(&scene.camera, 2.0);
+            match path.save(CAMERA_PATH) {
+                Ok(_) => println!(
+                    "{} keyframes saved to {}",
+                    path.keyframes.len(),
+                    CAMERA_PATH
+                ),
+                Err(e) => eprintln!("{:#}", e),
+            }
+            true
+        }
+        KeyCode::KeyL => {
+            if scene.camera_path.is_playing() {
+                scene.camera_path.stop();
+            } else {
+                scene.camera_path.play();
+            }
+            true
+        }
+        KeyCode::KeyF => {
+            let (min, max) = scene_bounds(scene);
+            match &mut scene.camera_controller {
+                CameraController::Orbit(orbit) => orbit.zoom_to_fit(min, max, &scene.projection),
+                CameraController::Arcball(arcball) => {
+                    arcball.zoom_to_fit(min, max, &scene.projection)
+                }
+                CameraController::Fly(_) => {}
+            }
+            true
+        }
+        _ => camera::input(engine, event),
+    }
+}
+
 pub fn init(engine: &mut Engine) {
+    engine.input_map = InputMap::load_or_default("resources/input_map.txt");
+    for (binding, names) in engine.input_map.conflicts() {
+        eprintln!("{} is bound to {}", binding, names.join(", "));
+    }
+
     let scene = &mut engine.scene;
     let device = &engine.device;
     let config = &engine.config;
@@ -35,8 +132,20 @@ pub fn init(engine: &mut Engine) {
     );
     scene.objects.push(fox_object);
 
-    let diffuse_bytes = include_bytes!("../../resources/fox/Texture.png");
-    let texture = texture::Texture::from_bytes(device, queue, diffuse_bytes, None).unwrap();
+    // the importer loads the material's textures, sRGB for the base color and linear for data maps
+    let mut textures = object::get_textures_from_model(
+        device,
+        queue,
+        "resources/fox/Fox.gltf",
+        &texture::TextureOptions::default(),
+        &mut engine.renderer.sampler_manager,
+    )
+    .unwrap();
+    let base_color = textures
+        .iter()
+        .position(|t| t.slot == object::TextureSlot::BaseColor)
+        .expect("the fox has a base color texture");
+    let texture = textures.swap_remove(base_color).texture;
 
     let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
         label: None,
@@ -377,7 +486,9 @@ fn update(engine: &mut Engine) {
     let dt = engine.last_render_time.elapsed();
     engine.last_render_time = instant::Instant::now();
 
-    scene.camera_controller.update_camera(&mut scene.camera, dt);
+    if !scene.camera_path.update(&mut scene.camera, dt) {
+        scene.camera_controller.update_camera(&mut scene.camera, dt);
+    }
     scene
         .camera_uniform
         .update_view_proj(&scene.camera, &scene.projection);
@@ -399,21 +510,29 @@ fn update(engine: &mut Engine) {
 }
 
 fn main() {
+    // the path recorded last time, if any
+    let mut camera_path = CameraPathPlayer::new(CameraPath::load(CAMERA_PATH).unwrap_or_default());
+    camera_path.looping = true;
+
     let scene = Scene {
         camera: camera::Camera::new(
             [0.0, 2.0, -5.0],
             cgmath::Rad(camera::SAFE_FRAC_PI_2),
             cgmath::Rad(-0.2),
         ),
-        camera_controller: Default::default(),
+        camera_controller: CameraController::Orbit(OrbitController::new([0.0, 1.0, 0.0], 5.0)),
         camera_uniform: Default::default(),
         projection: Default::default(),
+        main_view: Default::default(),
+        cameras: vec![],
+        camera_path,
         objects: vec![],
+        lights: vec![],
         pipeline_objects: vec![],
     };
 
     let commands = CommandBundle {
-        input_command: Box::new(camera::input),
+        input_command: Box::new(input),
         init_command: Box::new(init),
         render_command: Box::new(render),
         update_command: Box::new(update),
@@ -0,0 +1,633 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+use wgpu_renderer::antialias::AntiAliasPass;
+use wgpu_renderer::bloom::{BloomComposite, BloomPass};
+use wgpu_renderer::camera::{self, CameraUniform};
+use wgpu_renderer::engine::*;
+use wgpu_renderer::hdr::{self, Exposure, HdrPass};
+use wgpu_renderer::ibl::{self, EnvironmentMaps};
+use wgpu_renderer::light::{DirectionalLight, Light, LightKind, PointLight, SpotLight};
+use wgpu_renderer::object::{self, Vertex};
+use wgpu_renderer::pipeline::PipelineObject;
+use wgpu_renderer::sampler::SamplerDesc;
+use wgpu_renderer::scene::*;
+use wgpu_renderer::shadow::{ShadowPass, ShadowSettings};
+use wgpu_renderer::skybox::SkyboxPass;
+use wgpu_renderer::{pipeline, texture};
+use winit::event::{ElementState, KeyEvent, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+const MAX_OBJECTS: u64 = 16;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct ModelUniform {
+    model: [[f32; 4]; 4],
+}
+
+fn create_plane(device: &wgpu::Device, half_size: f32) -> object::Mesh {
+    let vertices = vec![
+        Vertex {
+            position: [-half_size, 0.0, -half_size],
+            color: [1.0; 3],
+            tex_coords: [0.0, 0.0],
+        },
+        Vertex {
+            position: [-half_size, 0.0, half_size],
+            color: [1.0; 3],
+            tex_coords: [0.0, 1.0],
+        },
+        Vertex {
+            position: [half_size, 0.0, half_size],
+            color: [1.0; 3],
+            tex_coords: [1.0, 1.0],
+        },
+        Vertex {
+            position: [half_size, 0.0, -half_size],
+            color: [1.0; 3],
+            tex_coords: [1.0, 0.0],
+        },
+    ];
+
+    object::Mesh::create_mesh(device, vertices, vec![0, 1, 2, 0, 2, 3])
+}
+
+// a Radiance panorama at `resources/sky.hdr`, only with the `hdr` feature
+fn load_sky_image() -> Option<image::DynamicImage> {
+    if !cfg!(feature = "hdr") {
+        return None;
+    }
+    image::open("resources/sky.hdr").ok()
+}
+
+// equirectangular HDR sky: a gradient from the horizon to the zenith over a dark ground
+fn create_sky_image(width: u32, height: u32) -> image::DynamicImage {
+    let horizon = [1.2, 1.1, 1.0];
+    let zenith = [0.15, 0.3, 0.8];
+    let ground = [0.08, 0.07, 0.06];
+
+    image::DynamicImage::ImageRgba32F(image::Rgba32FImage::from_fn(width, height, |_, y| {
+        // 1 at the top, -1 at the bottom
+        let elevation = 1.0 - 2.0 * (y as f32 + 0.5) / height as f32;
+        let color = if elevation >= 0.0 {
+            let t = elevation.sqrt();
+            [0, 1, 2].map(|i| horizon[i] + (zenith[i] - horizon[i]) * t)
+        } else {
+            let t = (-elevation * 8.0).min(1.0);
+            [0, 1, 2].map(|i| horizon[i] * 0.3 + (ground[i] - horizon[i] * 0.3) * t)
+        };
+        image::Rgba([color[0], color[1], color[2], 1.0])
+    }))
+}
+
+fn create_material_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &texture::Texture,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            },
+        ],
+    })
+}
+
+pub fn input(engine: &mut Engine, event: &WindowEvent) -> bool {
+    let state = &mut engine.renderer.state;
+
+    match event {
+        WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    physical_key: PhysicalKey::Code(key),
+                    state: ElementState::Pressed,
+                    ..
+                },
+            ..
+        } if matches!(
+            key,
+            KeyCode::KeyT
+                | KeyCode::KeyE
+                | KeyCode::ArrowUp
+                | KeyCode::ArrowDown
+                | KeyCode::KeyB
+                | KeyCode::KeyM
+                | KeyCode::BracketLeft
+                | KeyCode::BracketRight
+                | KeyCode::KeyN
+                | KeyCode::KeyF
+                | KeyCode::KeyK
+        ) =>
+        {
+            match key {
+                KeyCode::KeyT => {
+                    state.tonemapper = state.tonemapper.next();
+                    println!("tonemapper: {:?}", state.tonemapper);
+                }
+                KeyCode::KeyE => {
+                    state.exposure = match state.exposure {
+                        Exposure::Auto { .. } => Exposure::Manual { ev: 0.0 },
+                        Exposure::Manual { .. } => Exposure::auto(),
+                    };
+                    println!("exposure: {:?}", state.exposure);
+                }
+                KeyCode::KeyB => {
+                    state.bloom.enabled = !state.bloom.enabled;
+                    println!("bloom: {}", state.bloom.enabled);
+                }
+                KeyCode::KeyM => {
+                    // additive bloom only makes sense for the parts above the threshold
+                    state.bloom.composite = match state.bloom.composite {
+                        BloomComposite::EnergyConserving => BloomComposite::Additive,
+                        BloomComposite::Additive => BloomComposite::EnergyConserving,
+                    };
+                    (state.bloom.threshold, state.bloom.intensity) = match state.bloom.composite {
+                        BloomComposite::EnergyConserving => (0.0, 0.04),
+                        BloomComposite::Additive => (1.0, 0.5),
+                    };
+                    println!("bloom: {:?}", state.bloom);
+                }
+                KeyCode::KeyK => {
+                    state.skybox = !state.skybox;
+                    println!("skybox: {}", state.skybox);
+                }
+                KeyCode::KeyF => {
+                    state.anti_aliasing = state.anti_aliasing.next();
+                    println!("anti-aliasing: {:?}", state.anti_aliasing);
+                }
+                KeyCode::KeyN => {
+                    // unsupported counts fall back to the closest supported one when applied
+                    state.sample_count = match state.sample_count {
+                        1 => 2,
+                        2 => 4,
+                        4 => 8,
+                        _ => 1,
+                    };
+                    println!("MSAA: x{}", state.sample_count);
+                }
+                KeyCode::BracketLeft | KeyCode::BracketRight => {
+                    let step = if *key == KeyCode::BracketRight {
+                        0.5
+                    } else {
+                        -0.5
+                    };
+                    state.bloom.radius = (state.bloom.radius + step).max(0.5);
+                    println!("bloom radius: {}", state.bloom.radius);
+                }
+                _ => {
+                    let step = if *key == KeyCode::ArrowUp { 0.5 } else { -0.5 };
+                    match &mut state.exposure {
+                        Exposure::Manual { ev } => *ev += step,
+                        Exposure::Auto { compensation, .. } => *compensation += step,
+                    }
+                    println!("exposure: {:?}", state.exposure);
+                }
+            }
+            true
+        }
+        _ => camera::input(engine, event),
+    }
+}
+
+pub fn init(engine: &mut Engine) {
+    let downlevel = engine.downlevel_capabilities();
+    let scene = &mut engine.scene;
+    let device = &engine.device;
+    let config = &engine.config;
+    let queue = &engine.queue;
+
+    let fox_object = object::get_object_from_mesh(
+        object::get_mesh_from_model(device, "resources/fox/Fox.gltf", 0.02),
+        [0.0; 3],
+    );
+    let ground_object = object::get_object_from_mesh(create_plane(device, 50.0), [0.0; 3]);
+    scene.objects.push(fox_object);
+    scene.objects.push(ground_object);
+
+    // mipmapped with anisotropic filtering, the fur doesn't shimmer when the fox is far away
+    let fox_texture = texture::Texture::from_bytes_with_options(
+        device,
+        queue,
+        include_bytes!("../../resources/fox/Texture.png"),
+        &texture::TextureOptions {
+            mipmaps: true,
+            sampler: SamplerDesc::repeat(16),
+            ..Default::default()
+        },
+        &mut engine.renderer.sampler_manager,
+        None,
+    )
+    .unwrap();
+    let white =
+        image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4])));
+    let ground_texture = texture::Texture::from_image(device, queue, &white, None).unwrap();
+
+    let material_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+    let camera = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+    });
+
+    let model_alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+    let model = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: model_alignment * MAX_OBJECTS,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let transform_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<ModelUniform>() as u64,
+                        ),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+    let transform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &transform_bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(camera.as_entire_buffer_binding()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &model,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<ModelUniform>() as u64),
+                }),
+            },
+        ],
+    });
+
+    let shadow_pass = ShadowPass::new(device, &downlevel, ShadowSettings::default());
+
+    let sky = texture::Texture::cube_from_equirectangular(
+        device,
+        queue,
+        &load_sky_image().unwrap_or_else(|| create_sky_image(1024, 512)),
+        256,
+        Some("Sky"),
+    );
+    // prefiltering takes a moment, later runs load the maps from disk
+    let environment_maps =
+        EnvironmentMaps::load_or_generate(device, queue, &sky, "target/ibl_cache.bin");
+
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: wgpu::ShaderSource::Wgsl(
+            (shadow_pass.shader_snippet(2)
+                + &ibl::shader_snippet(3)
+                + include_str!("postprocess.wgsl"))
+            .into(),
+        ),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Pipeline Layout"),
+        bind_group_layouts: &[
+            &material_bind_group_layout,
+            &transform_bind_group_layout,
+            &shadow_pass.bind_group_layout,
+            &environment_maps.bind_group_layout,
+        ],
+        push_constant_ranges: &[],
+    });
+
+    // kept by the pipeline manager, rebuilds the pipelines when the MSAA sample count changes
+    let builder: pipeline::PipelineBuilder = Box::new(move |device, sample_count| {
+        let create_pipeline = |polygon_mode| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Render Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[Vertex::desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: hdr::HDR_FORMAT,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: Some(wgpu::Face::Back),
+                    unclipped_depth: false,
+                    polygon_mode,
+                    conservative: false,
+                },
+                depth_stencil: pipeline::create_depth_stencil_state(),
+                multisample: pipeline::create_multisample_state(sample_count),
+                multiview: None,
+            })
+        };
+
+        vec![
+            PipelineObject {
+                pipeline: create_pipeline(wgpu::PolygonMode::Fill),
+                polygon_mode: wgpu::PolygonMode::Fill,
+            },
+            PipelineObject {
+                pipeline: create_pipeline(wgpu::PolygonMode::Line),
+                polygon_mode: wgpu::PolygonMode::Line,
+            },
+        ]
+    });
+
+    let renderer = &mut engine.renderer;
+    renderer.bind_group_manager.add_bind_group(
+        0,
+        create_material_bind_group(device, &material_bind_group_layout, &fox_texture),
+    );
+    renderer.bind_group_manager.add_bind_group(
+        1,
+        create_material_bind_group(device, &material_bind_group_layout, &ground_texture),
+    );
+    renderer
+        .bind_group_manager
+        .add_bind_group(2, transform_bind_group);
+
+    renderer.buffer_manager.add_buffer(0, camera);
+    renderer.buffer_manager.add_buffer(1, model);
+
+    renderer.shadow_pass = Some(shadow_pass);
+    renderer.state.sample_count = 4;
+    renderer
+        .pipeline_manager
+        .set_builder(device, renderer.sample_count(), builder);
+    let hdr_pass = HdrPass::new(device, config);
+    let depth_texture = texture::Texture::create_depth_texture(device, config, None);
+    renderer.bloom_pass = Some(BloomPass::new(device, &hdr_pass.target));
+    renderer.antialias_pass = Some(AntiAliasPass::new(device, &hdr_pass.target, &depth_texture));
+    renderer.hdr_pass = Some(hdr_pass);
+    renderer.depth_texture = Some(depth_texture);
+
+    renderer.skybox_pass = Some(SkyboxPass::new(
+        device,
+        hdr::HDR_FORMAT,
+        renderer.sample_count(),
+        &sky,
+    ));
+    renderer.environment_maps = Some(environment_maps);
+}
+
+pub fn render(engine: &Engine) -> Result<(), wgpu::SurfaceError> {
+    let surface = &engine.surface;
+    let device = &engine.device;
+    let queue = &engine.queue;
+    let renderer = &engine.renderer;
+    let shadow_pass = renderer.shadow_pass.as_ref().unwrap();
+    let hdr_pass = renderer.hdr_pass.as_ref().unwrap();
+    let bloom_pass = renderer.bloom_pass.as_ref().unwrap();
+    let antialias_pass = renderer.antialias_pass.as_ref().unwrap();
+    let environment_maps = renderer.environment_maps.as_ref().unwrap();
+
+    let output = surface.get_current_texture()?;
+    let view = output
+        .texture
+        .create_view(&wgpu::TextureViewDescriptor::default());
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Render Encoder"),
+    });
+
+    shadow_pass.render(&mut encoder, &engine.scene.objects);
+
+    let depth_stencil_attachment =
+        renderer
+            .depth_texture
+            .as_ref()
+            .map(|texture| wgpu::RenderPassDepthStencilAttachment {
+                view: &texture.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            });
+
+    {
+        let mut _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(renderer.color_attachment(&hdr_pass.target.view))],
+            depth_stencil_attachment,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        if renderer.state.polygon_fill {
+            _render_pass.set_pipeline(renderer.pipeline_manager.first_polygon_fill_item());
+        } else {
+            _render_pass.set_pipeline(renderer.pipeline_manager.first_polygon_line_item());
+        }
+
+        _render_pass.set_bind_group(2, &shadow_pass.bind_group, &[]);
+        _render_pass.set_bind_group(3, &environment_maps.bind_group, &[]);
+
+        let model_alignment = device.limits().min_uniform_buffer_offset_alignment;
+        for (i, object) in engine.scene.objects.iter().enumerate() {
+            let material = renderer.bind_group_manager.find_by_id(i as u32).unwrap();
+            _render_pass.set_bind_group(0, &material.bind_group, &[]);
+            _render_pass.set_bind_group(
+                1,
+                &renderer
+                    .bind_group_manager
+                    .find_by_id(2)
+                    .unwrap()
+                    .bind_group,
+                &[model_alignment * i as u32],
+            );
+
+            _render_pass.set_vertex_buffer(0, object.mesh.vertex_buffer.slice(..));
+            _render_pass.set_index_buffer(
+                object.mesh.index_buffer.slice(..),
+                wgpu::IndexFormat::Uint16,
+            );
+            _render_pass.draw_indexed(0..object.mesh.indices.len() as u32, 0, 0..1);
+        }
+
+        // after the opaque geometry, fills whatever is still at the far plane
+        if let Some(skybox_pass) = &renderer.skybox_pass {
+            skybox_pass.draw(&mut _render_pass);
+        }
+    }
+
+    antialias_pass.render(&mut encoder, &hdr_pass.target);
+    bloom_pass.render(&mut encoder, &hdr_pass.target);
+    hdr_pass.render(&mut encoder, &view);
+
+    queue.submit(std::iter::once(encoder.finish()));
+    output.present();
+
+    Ok(())
+}
+
+fn update(engine: &mut Engine) {
+    let renderer = &mut engine.renderer;
+    let scene = &mut engine.scene;
+    let device = &engine.device;
+    let queue = &engine.queue;
+
+    let dt = engine.last_render_time.elapsed();
+    engine.last_render_time = instant::Instant::now();
+
+    scene.camera_controller.update_camera(&mut scene.camera, dt);
+    // sets the TAA jitter, has to happen before the camera uniform is built from the projection
+    if let Some(antialias_pass) = renderer.antialias_pass.as_mut() {
+        antialias_pass.update(queue, &renderer.state, scene);
+    }
+    scene
+        .camera_uniform
+        .update_view_proj(&scene.camera, &scene.projection);
+    if let Some(skybox_pass) = renderer.skybox_pass.as_mut() {
+        skybox_pass.update(queue, &renderer.state, scene);
+    }
+
+    queue.write_buffer(
+        &renderer.buffer_manager.find_by_id(0).unwrap().buffer,
+        0,
+        bytemuck::cast_slice(&[scene.camera_uniform]),
+    );
+
+    let model_alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+    for (i, object) in scene.objects.iter().enumerate() {
+        queue.write_buffer(
+            &renderer.buffer_manager.find_by_id(1).unwrap().buffer,
+            model_alignment * i as u64,
+            bytemuck::cast_slice(&[ModelUniform {
+                model: object.model_matrix().into(),
+            }]),
+        );
+    }
+
+    // swing the sun around so the shadows visibly move
+    let elapsed = engine.start_time.elapsed().as_secs_f32() * 0.3;
+    if let Some(Light {
+        kind: LightKind::Directional(sun),
+        ..
+    }) = scene.lights.first_mut()
+    {
+        sun.direction = cgmath::Vector3::new(elapsed.cos(), -1.5, elapsed.sin());
+    }
+
+    if let Some(shadow_pass) = renderer.shadow_pass.as_mut() {
+        shadow_pass.update(device, queue, scene);
+    }
+
+    if let Some(bloom_pass) = renderer.bloom_pass.as_mut() {
+        bloom_pass.update(queue, &renderer.state);
+    }
+
+    if let Some(hdr_pass) = renderer.hdr_pass.as_mut() {
+        hdr_pass.update(queue, &renderer.state, dt);
+    }
+}
+
+fn main() {
+    let scene = Scene {
+        camera: camera::Camera::new(
+            [0.0, 3.0, -7.0],
+            cgmath::Rad(camera::SAFE_FRAC_PI_2),
+            cgmath::Rad(-0.3),
+        ),
+        camera_controller: Default::default(),
+        camera_uniform: Default::default(),
+        projection: Default::default(),
+        main_view: Default::default(),
+        cameras: vec![],
+        camera_path: Default::default(),
+        objects: vec![],
+        lights: vec![
+            Light::directional(DirectionalLight {
+                intensity: 3.0,
+                ..Default::default()
+            }),
+            Light::spot(SpotLight {
+                position: cgmath::Point3::new(3.0, 4.0, 2.0),
+                direction: cgmath::Vector3::new(-3.0, -4.0, -2.0),
+                color: [1.0, 0.6, 0.3],
+                intensity: 200.0,
+                ..Default::default()
+            }),
+            Light::point(PointLight {
+                position: cgmath::Point3::new(-2.0, 1.5, 1.0),
+                color: [0.3, 0.5, 1.0],
+                intensity: 60.0,
+                range: 8.0,
+            }),
+        ],
+        pipeline_objects: vec![],
+    };
+
+    let commands = CommandBundle {
+        input_command: Box::new(input),
+        init_command: Box::new(init),
+        render_command: Box::new(render),
+        update_command: Box::new(update),
+    };
+
+    pollster::block_on(run(Some(scene), commands));
+}
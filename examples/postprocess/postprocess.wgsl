@@ -0,0 +1,60 @@
+// prepended with `ShadowPass::shader_snippet(2)`, which provides `shade`,
+// and `ibl::shader_snippet(3)`, which provides `ibl_ambient`
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+
+@group(0) @binding(1)
+var s_diffuse: sampler;
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct ModelUniform {
+    model: mat4x4<f32>,
+}
+
+@group(1) @binding(1)
+var<uniform> model: ModelUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_position: vec3<f32>,
+}
+
+@vertex
+fn vs_main(vertex: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    let world_position = model.model * vec4<f32>(vertex.position, 1.0);
+    out.tex_coords = vertex.tex_coords;
+    out.world_position = world_position.xyz;
+    out.clip_position = camera.view_proj * world_position;
+    return out;
+}
+
+const ROUGHNESS: f32 = 0.8;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // the vertex format carries no normals, use the flat face normal instead
+    let normal = normalize(cross(dpdy(in.world_position), dpdx(in.world_position)));
+    let albedo = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+
+    // written to the HDR target unclamped, the tonemap pass maps it to the display
+    let view = normalize(shadows.camera_position.xyz - in.world_position);
+    let ambient = ibl_ambient(normal, view, albedo.rgb, ROUGHNESS, 0.0);
+    let color = albedo.rgb * shade(in.world_position, normal) + ambient;
+
+    return vec4<f32>(color, albedo.a);
+}
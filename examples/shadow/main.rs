@@ -0,0 +1,673 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+use wgpu_renderer::camera::{self, CameraUniform};
+use wgpu_renderer::engine::*;
+use wgpu_renderer::light::{DirectionalLight, Light, LightKind, PointLight, SpotLight};
+use wgpu_renderer::object::{self, Vertex};
+use wgpu_renderer::pipeline::PipelineObject;
+use wgpu_renderer::render_target::{RenderTarget, RenderTargetDesc, TargetSize};
+use wgpu_renderer::scene::*;
+use wgpu_renderer::shadow::{ShadowPass, ShadowSettings};
+use wgpu_renderer::viewport::{CameraView, ViewClear, ViewTarget, Viewport};
+use wgpu_renderer::{pipeline, texture};
+use winit::event::{ElementState, KeyEvent, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+const MAX_OBJECTS: u64 = 16;
+// object showing what the security camera sees
+const SCREEN: usize = 2;
+const TRANSFORM_BIND_GROUP: u32 = MAX_OBJECTS as u32;
+const SECURITY_TARGET: u32 = 0;
+// indices into `Scene::cameras`
+const SECURITY_VIEW: usize = 0;
+const MINIMAP_VIEW: usize = 1;
+const SECURITY_CAMERA_POSITION: [f32; 3] = [5.0, 4.0, -4.0];
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct ModelUniform {
+    model: [[f32; 4]; 4],
+}
+
+fn create_plane(device: &wgpu::Device, half_size: f32) -> object::Mesh {
+    let vertices = vec![
+        Vertex {
+            position: [-half_size, 0.0, -half_size],
+            color: [1.0; 3],
+            tex_coords: [0.0, 0.0],
+        },
+        Vertex {
+            position: [-half_size, 0.0, half_size],
+            color: [1.0; 3],
+            tex_coords: [0.0, 1.0],
+        },
+        Vertex {
+            position: [half_size, 0.0, half_size],
+            color: [1.0; 3],
+            tex_coords: [1.0, 1.0],
+        },
+        Vertex {
+            position: [half_size, 0.0, -half_size],
+            color: [1.0; 3],
+            tex_coords: [1.0, 0.0],
+        },
+    ];
+
+    object::Mesh::create_mesh(device, vertices, vec![0, 1, 2, 0, 2, 3])
+}
+
+// upright quad facing -z, the top edge maps to the top row of the texture
+fn create_screen(device: &wgpu::Device, width: f32, height: f32) -> object::Mesh {
+    let half_width = width / 2.0;
+    let vertices = vec![
+        Vertex {
+            position: [half_width, height, 0.0],
+            color: [1.0; 3],
+            tex_coords: [0.0, 0.0],
+        },
+        Vertex {
+            position: [half_width, 0.0, 0.0],
+            color: [1.0; 3],
+            tex_coords: [0.0, 1.0],
+        },
+        Vertex {
+            position: [-half_width, 0.0, 0.0],
+            color: [1.0; 3],
+            tex_coords: [1.0, 1.0],
+        },
+        Vertex {
+            position: [-half_width, height, 0.0],
+            color: [1.0; 3],
+            tex_coords: [1.0, 0.0],
+        },
+    ];
+
+    object::Mesh::create_mesh(device, vertices, vec![0, 1, 2, 0, 2, 3])
+}
+
+// slowly pans left and right around the fox
+fn security_camera(elapsed: f32) -> camera::Camera {
+    let [x, y, z] = SECURITY_CAMERA_POSITION;
+    let yaw = (-z).atan2(-x) + 0.4 * (elapsed * 0.5).sin();
+    let pitch = (-y).atan2((x * x + z * z).sqrt());
+    camera::Camera::new([x, y, z], cgmath::Rad(yaw), cgmath::Rad(pitch))
+}
+
+// transform bind group and camera buffer of `Scene::cameras[view]`
+fn view_bind_group_id(view: usize) -> u32 {
+    TRANSFORM_BIND_GROUP + 1 + view as u32
+}
+
+fn view_buffer_id(view: usize) -> u32 {
+    2 + view as u32
+}
+
+fn create_transform_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    camera: &wgpu::Buffer,
+    model: &wgpu::Buffer,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(camera.as_entire_buffer_binding()),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: model,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<ModelUniform>() as u64),
+                }),
+            },
+        ],
+    })
+}
+
+fn create_material_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &texture::Texture,
+) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            },
+        ],
+    })
+}
+
+pub fn input(engine: &mut Engine, event: &WindowEvent) -> bool {
+    match event {
+        WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    physical_key: PhysicalKey::Code(KeyCode::KeyC),
+                    state: ElementState::Pressed,
+                    ..
+                },
+            ..
+        } => {
+            if let Some(shadow_pass) = engine.renderer.shadow_pass.as_mut() {
+                shadow_pass.settings.debug_cascades = !shadow_pass.settings.debug_cascades;
+            }
+            true
+        }
+        WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    physical_key: PhysicalKey::Code(KeyCode::KeyO),
+                    state: ElementState::Pressed,
+                    ..
+                },
+            ..
+        } => {
+            let projection = &mut engine.scene.projection;
+            projection.set_kind(match projection.kind() {
+                camera::ProjectionKind::Perspective { .. } => {
+                    camera::ProjectionKind::Orthographic {
+                        scaling: camera::OrthographicScaling::FixedHeight(12.0),
+                    }
+                }
+                camera::ProjectionKind::Orthographic { .. } => {
+                    camera::ProjectionKind::Perspective {
+                        fovy: cgmath::Deg(45.0).into(),
+                    }
+                }
+            });
+            true
+        }
+        WindowEvent::KeyboardInput {
+            event:
+                KeyEvent {
+                    physical_key: PhysicalKey::Code(KeyCode::KeyI),
+                    state: ElementState::Pressed,
+                    ..
+                },
+            ..
+        } => {
+            let stats = &engine.renderer.cull_stats;
+            println!(
+                "last frame drew {} objects and culled {} over all camera views",
+                stats.visible(),
+                stats.culled()
+            );
+            true
+        }
+        _ => camera::input(engine, event),
+    }
+}
+
+pub fn init(engine: &mut Engine) {
+    let downlevel = engine.downlevel_capabilities();
+    let scene = &mut engine.scene;
+    let device = &engine.device;
+    let config = &engine.config;
+    let queue = &engine.queue;
+
+    let fox_object = object::get_object_from_mesh(
+        object::get_mesh_from_model(device, "resources/fox/Fox.gltf", 0.02),
+        [0.0; 3],
+    );
+    let ground_object = object::get_object_from_mesh(create_plane(device, 50.0), [0.0; 3]);
+    let screen_object =
+        object::get_object_from_mesh(create_screen(device, 3.2, 1.8), [0.0, 1.0, 4.0]);
+    scene.objects.push(fox_object);
+    scene.objects.push(ground_object);
+    scene.objects.push(screen_object);
+
+    // half the window resolution is plenty for a screen in the scene, and keeps it cheap
+    let security_target = RenderTarget::new(
+        device,
+        &RenderTargetDesc {
+            label: Some("Security Camera"),
+            size: TargetSize::Window { scale: 0.5 },
+            color_formats: &[config.format],
+            depth: true,
+            sample_count: engine.renderer.sample_count(),
+        },
+        config,
+    )
+    .unwrap();
+
+    scene.cameras.insert(
+        SECURITY_VIEW,
+        CameraView::new(
+            security_camera(0.0),
+            camera::Projection::new(1, 1, cgmath::Deg(60.0), 0.1, 100.0),
+        )
+        .order(-1)
+        .target(ViewTarget::Texture(SECURITY_TARGET)),
+    );
+    // top down view in the top right corner, drawn over the main view
+    scene.cameras.insert(
+        MINIMAP_VIEW,
+        CameraView::new(
+            camera::Camera::new(
+                [0.0, 20.0, 0.0],
+                cgmath::Rad(std::f32::consts::FRAC_PI_2),
+                cgmath::Rad(-camera::SAFE_FRAC_PI_2),
+            ),
+            camera::Projection::orthographic(
+                1,
+                1,
+                camera::OrthographicScaling::FixedHeight(30.0),
+                0.1,
+                100.0,
+            ),
+        )
+        .viewport(Viewport::new(0.74, 0.04, 0.22, 0.22))
+        .order(1)
+        .clear(ViewClear::depth_only()),
+    );
+
+    let fox_texture = texture::Texture::from_bytes(
+        device,
+        queue,
+        include_bytes!("../../resources/fox/Texture.png"),
+        None,
+    )
+    .unwrap();
+    let white =
+        image::DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(1, 1, image::Rgba([255; 4])));
+    let ground_texture = texture::Texture::from_image(device, queue, &white, None).unwrap();
+
+    let material_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+    let camera = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+    });
+
+    let model_alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+    let model = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: model_alignment * MAX_OBJECTS,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let transform_bind_group_layout =
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<ModelUniform>() as u64,
+                        ),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+    let transform_bind_group =
+        create_transform_bind_group(device, &transform_bind_group_layout, &camera, &model);
+    let view_transforms: Vec<(wgpu::Buffer, wgpu::BindGroup)> = scene
+        .cameras
+        .iter()
+        .map(|_| {
+            let camera = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                contents: bytemuck::cast_slice(&[CameraUniform::new()]),
+            });
+            let bind_group =
+                create_transform_bind_group(device, &transform_bind_group_layout, &camera, &model);
+            (camera, bind_group)
+        })
+        .collect();
+
+    let shadow_pass = ShadowPass::new(device, &downlevel, ShadowSettings::default());
+
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: wgpu::ShaderSource::Wgsl(
+            (shadow_pass.shader_snippet(2) + include_str!("shadow.wgsl")).into(),
+        ),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("Pipeline Layout"),
+        bind_group_layouts: &[
+            &material_bind_group_layout,
+            &transform_bind_group_layout,
+            &shadow_pass.bind_group_layout,
+        ],
+        push_constant_ranges: &[],
+    });
+
+    let create_pipeline = |polygon_mode| {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                unclipped_depth: false,
+                polygon_mode,
+                conservative: false,
+            },
+            depth_stencil: pipeline::create_depth_stencil_state(),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        })
+    };
+
+    let mut pipelines = vec![
+        PipelineObject {
+            pipeline: create_pipeline(wgpu::PolygonMode::Fill),
+            polygon_mode: wgpu::PolygonMode::Fill,
+        },
+        PipelineObject {
+            pipeline: create_pipeline(wgpu::PolygonMode::Line),
+            polygon_mode: wgpu::PolygonMode::Line,
+        },
+    ];
+    scene.update_pipelines(&mut pipelines);
+
+    let renderer = &mut engine.renderer;
+    renderer.bind_group_manager.add_bind_group(
+        0,
+        create_material_bind_group(device, &material_bind_group_layout, &fox_texture),
+    );
+    renderer.bind_group_manager.add_bind_group(
+        1,
+        create_material_bind_group(device, &material_bind_group_layout, &ground_texture),
+    );
+    renderer
+        .bind_group_manager
+        .add_bind_group(TRANSFORM_BIND_GROUP, transform_bind_group);
+
+    renderer.buffer_manager.add_buffer(0, camera);
+    renderer.buffer_manager.add_buffer(1, model);
+
+    for (i, (camera, bind_group)) in view_transforms.into_iter().enumerate() {
+        renderer
+            .buffer_manager
+            .add_buffer(view_buffer_id(i), camera);
+        renderer
+            .bind_group_manager
+            .add_bind_group(view_bind_group_id(i), bind_group);
+    }
+
+    renderer
+        .render_target_manager
+        .add_render_target(SECURITY_TARGET, security_target);
+
+    renderer.shadow_pass = Some(shadow_pass);
+    renderer.depth_texture = Some(texture::Texture::create_depth_texture(device, config, None));
+}
+
+// Draws the scene as seen by `view`.
+// A view into the security camera target leaves out the screen, which samples that target
+fn draw_objects<'a>(render_pass: &mut wgpu::RenderPass<'a>, engine: &'a Engine, view: SceneView) {
+    let renderer = &engine.renderer;
+    let model_alignment = engine.device.limits().min_uniform_buffer_offset_alignment;
+    let security_target = &renderer
+        .render_target_manager
+        .find_by_id(SECURITY_TARGET)
+        .unwrap()
+        .render_target;
+
+    let (transform, frustum) = match view {
+        SceneView::Main => (TRANSFORM_BIND_GROUP, engine.scene.camera_uniform.frustum()),
+        SceneView::Camera(i) => (
+            view_bind_group_id(i),
+            engine.scene.cameras[i].uniform.frustum(),
+        ),
+    };
+    let skip = (engine.scene.view_settings(view).target == ViewTarget::Texture(SECURITY_TARGET))
+        .then_some(SCREEN);
+    let transform = &renderer
+        .bind_group_manager
+        .find_by_id(transform)
+        .unwrap()
+        .bind_group;
+
+    if renderer.state.polygon_fill {
+        render_pass.set_pipeline(renderer.pipeline_manager.first_polygon_fill_item());
+    } else {
+        render_pass.set_pipeline(renderer.pipeline_manager.first_polygon_line_item());
+    }
+    render_pass.set_bind_group(2, &renderer.shadow_pass.as_ref().unwrap().bind_group, &[]);
+
+    let objects = &engine.scene.objects;
+    for i in frustum.visible_objects(objects, &renderer.cull_stats) {
+        if Some(i) == skip {
+            continue;
+        }
+        let object = &objects[i];
+
+        let material = if i == SCREEN {
+            &security_target.bind_group
+        } else {
+            &renderer
+                .bind_group_manager
+                .find_by_id(i as u32)
+                .unwrap()
+                .bind_group
+        };
+        render_pass.set_bind_group(0, material, &[]);
+        render_pass.set_bind_group(1, transform, &[model_alignment * i as u32]);
+
+        render_pass.set_vertex_buffer(0, object.mesh.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(
+            object.mesh.index_buffer.slice(..),
+            wgpu::IndexFormat::Uint16,
+        );
+        render_pass.draw_indexed(0..object.mesh.indices.len() as u32, 0, 0..1);
+    }
+}
+
+pub fn render(engine: &Engine) -> Result<(), wgpu::SurfaceError> {
+    let surface = &engine.surface;
+    let device = &engine.device;
+    let queue = &engine.queue;
+    let renderer = &engine.renderer;
+    let shadow_pass = renderer.shadow_pass.as_ref().unwrap();
+
+    let output = surface.get_current_texture()?;
+    let output_view = output
+        .texture
+        .create_view(&wgpu::TextureViewDescriptor::default());
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Render Encoder"),
+    });
+
+    shadow_pass.render(&mut encoder, &engine.scene.objects);
+
+    let window_size = (engine.config.width, engine.config.height);
+
+    // views into render targets run first, so the main view can sample them
+    for view in engine.scene.ordered_views() {
+        let mut view_pass = engine
+            .scene
+            .view_settings(view)
+            .begin_render_pass(&mut encoder, renderer, &output_view, window_size)
+            .unwrap();
+        draw_objects(&mut view_pass, engine, view);
+    }
+
+    queue.submit(std::iter::once(encoder.finish()));
+    output.present();
+
+    Ok(())
+}
+
+fn update(engine: &mut Engine) {
+    let renderer = &mut engine.renderer;
+    let scene = &mut engine.scene;
+    let device = &engine.device;
+    let queue = &engine.queue;
+
+    let dt = engine.last_render_time.elapsed();
+    engine.last_render_time = instant::Instant::now();
+
+    scene.camera_controller.update_camera(&mut scene.camera, dt);
+    scene
+        .camera_uniform
+        .update_view_proj(&scene.camera, &scene.projection);
+
+    queue.write_buffer(
+        &renderer.buffer_manager.find_by_id(0).unwrap().buffer,
+        0,
+        bytemuck::cast_slice(&[scene.camera_uniform]),
+    );
+
+    scene.cameras[SECURITY_VIEW].camera =
+        security_camera(engine.start_time.elapsed().as_secs_f32());
+    scene.update_cameras();
+    for (i, camera_view) in scene.cameras.iter().enumerate() {
+        queue.write_buffer(
+            &renderer
+                .buffer_manager
+                .find_by_id(view_buffer_id(i))
+                .unwrap()
+                .buffer,
+            0,
+            bytemuck::cast_slice(&[camera_view.uniform]),
+        );
+    }
+
+    let model_alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+    for (i, object) in scene.objects.iter().enumerate() {
+        queue.write_buffer(
+            &renderer.buffer_manager.find_by_id(1).unwrap().buffer,
+            model_alignment * i as u64,
+            bytemuck::cast_slice(&[ModelUniform {
+                model: object.model_matrix().into(),
+            }]),
+        );
+    }
+
+    // swing the sun around so the shadows visibly move
+    let elapsed = engine.start_time.elapsed().as_secs_f32() * 0.3;
+    if let Some(Light {
+        kind: LightKind::Directional(sun),
+        ..
+    }) = scene.lights.first_mut()
+    {
+        sun.direction = cgmath::Vector3::new(elapsed.cos(), -1.5, elapsed.sin());
+    }
+
+    if let Some(shadow_pass) = renderer.shadow_pass.as_mut() {
+        shadow_pass.update(device, queue, scene);
+    }
+}
+
+fn main() {
+    let scene = Scene {
+        camera: camera::Camera::new(
+            [0.0, 3.0, -7.0],
+            cgmath::Rad(camera::SAFE_FRAC_PI_2),
+            cgmath::Rad(-0.3),
+        ),
+        camera_controller: Default::default(),
+        camera_uniform: Default::default(),
+        projection: Default::default(),
+        main_view: Default::default(),
+        cameras: vec![],
+        camera_path: Default::default(),
+        objects: vec![],
+        lights: vec![
+            Light::directional(DirectionalLight {
+                intensity: 0.8,
+                ..Default::default()
+            }),
+            Light::spot(SpotLight {
+                position: cgmath::Point3::new(3.0, 4.0, 2.0),
+                direction: cgmath::Vector3::new(-3.0, -4.0, -2.0),
+                color: [1.0, 0.6, 0.3],
+                intensity: 30.0,
+                ..Default::default()
+            }),
+            Light::point(PointLight {
+                position: cgmath::Point3::new(-2.0, 1.5, 1.0),
+                color: [0.3, 0.5, 1.0],
+                intensity: 10.0,
+                range: 8.0,
+            }),
+        ],
+        pipeline_objects: vec![],
+    };
+
+    let commands = CommandBundle {
+        input_command: Box::new(input),
+        init_command: Box::new(init),
+        render_command: Box::new(render),
+        update_command: Box::new(update),
+    };
+
+    pollster::block_on(run(Some(scene), commands));
+}
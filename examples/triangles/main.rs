@@ -246,8 +246,12 @@ fn main() {
         camera_controller: Default::default(),
         camera_uniform: Default::default(),
         objects: vec![],
+        lights: vec![],
         pipeline_objects: vec![],
         projection: Default::default(),
+        main_view: Default::default(),
+        cameras: vec![],
+        camera_path: Default::default(),
     };
 
     let commands = CommandBundle {
@@ -0,0 +1,528 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::*;
+
+use crate::hdr::HDR_FORMAT;
+use crate::renderer::RenderState;
+use crate::scene::Scene;
+use crate::texture;
+
+// length of the jitter sequence
+const TAA_SAMPLES: u32 = 8;
+// weight of the current frame against the history
+const TAA_BLEND: f32 = 0.1;
+const MOTION_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+/// Post process anti-aliasing, independent of MSAA (`RenderState::sample_count`)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AntiAliasing {
+    None,
+    /// single full screen pass smoothing along luma edges
+    Fxaa,
+    /// jitters the projection every frame and accumulates the frames, also covers shader aliasing.
+    /// Reprojects with the depth buffer, so MSAA is suspended while it's on
+    Taa,
+}
+
+impl AntiAliasing {
+    /// next mode, for cycling through them at runtime
+    pub fn next(self) -> Self {
+        match self {
+            AntiAliasing::None => AntiAliasing::Fxaa,
+            AntiAliasing::Fxaa => AntiAliasing::Taa,
+            AntiAliasing::Taa => AntiAliasing::None,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct TaaParams {
+    inv_view_proj: [[f32; 4]; 4],
+    unjittered_view_proj: [[f32; 4]; 4],
+    prev_view_proj: [[f32; 4]; 4],
+    blend: f32,
+    reset: u32,
+    _padding: [f32; 2],
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// FXAA and TAA on the HDR target, the mode is read from `RenderState::anti_aliasing`.
+///
+/// Both write into an intermediate texture which is copied back into the target, so the passes
+/// after it (bloom, tonemapping) don't change. TAA keeps two history textures and swaps them
+/// every frame, and fills `motion_target` with camera motion reconstructed from depth.
+pub struct AntiAliasPass {
+    pub motion_target: texture::Texture,
+    history: [texture::Texture; 2],
+    fxaa_pipeline: wgpu::RenderPipeline,
+    fxaa_bind_group_layout: wgpu::BindGroupLayout,
+    fxaa_bind_group: wgpu::BindGroup,
+    motion_pipeline: wgpu::RenderPipeline,
+    motion_bind_group_layout: wgpu::BindGroupLayout,
+    // only when the depth texture is single sampled
+    motion_bind_group: Option<wgpu::BindGroup>,
+    resolve_pipeline: wgpu::RenderPipeline,
+    resolve_bind_group_layout: wgpu::BindGroupLayout,
+    // resolve_bind_groups[i] reads history[1 - i] and renders into history[i]
+    resolve_bind_groups: [wgpu::BindGroup; 2],
+    params_buffer: wgpu::Buffer,
+    mode: AntiAliasing,
+    frame: u32,
+    prev_view_proj: Matrix4<f32>,
+    reset: bool,
+}
+
+impl AntiAliasPass {
+    /// `target` is the HDR scene target, `depth` the depth texture the scene was rendered with
+    pub fn new(device: &wgpu::Device, target: &texture::Texture, depth: &texture::Texture) -> Self {
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("TAA Params"),
+            size: std::mem::size_of::<TaaParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let texture_entry = |binding, filterable| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        let params_entry = wgpu::BindGroupLayoutEntry {
+            binding: 4,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let fxaa_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("FXAA Layout"),
+                entries: &[texture_entry(0, true), sampler_entry(1)],
+            });
+        let motion_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("TAA Motion Layout"),
+                entries: &[
+                    params_entry,
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 5,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let resolve_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("TAA Resolve Layout"),
+                entries: &[
+                    texture_entry(0, true),
+                    texture_entry(1, true),
+                    texture_entry(2, false),
+                    sampler_entry(3),
+                    params_entry,
+                ],
+            });
+
+        let fxaa_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("FXAA Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("shaders/fullscreen.wgsl"),
+                    include_str!("shaders/fxaa.wgsl")
+                )
+                .into(),
+            ),
+        });
+        let taa_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("TAA Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("shaders/fullscreen.wgsl"),
+                    include_str!("shaders/taa.wgsl")
+                )
+                .into(),
+            ),
+        });
+
+        let create_pipeline = |label: &str,
+                               layout: &wgpu::BindGroupLayout,
+                               shader: &wgpu::ShaderModule,
+                               entry_point: &str,
+                               format: wgpu::TextureFormat| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "vs_fullscreen",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+
+        let fxaa_pipeline = create_pipeline(
+            "FXAA Pipeline",
+            &fxaa_bind_group_layout,
+            &fxaa_shader,
+            "fs_fxaa",
+            HDR_FORMAT,
+        );
+        let motion_pipeline = create_pipeline(
+            "TAA Motion Pipeline",
+            &motion_bind_group_layout,
+            &taa_shader,
+            "fs_motion",
+            MOTION_FORMAT,
+        );
+        let resolve_pipeline = create_pipeline(
+            "TAA Resolve Pipeline",
+            &resolve_bind_group_layout,
+            &taa_shader,
+            "fs_resolve",
+            HDR_FORMAT,
+        );
+
+        let (motion_target, history) = Self::create_targets(device, target);
+        let fxaa_bind_group = Self::create_fxaa_bind_group(device, &fxaa_bind_group_layout, target);
+        let motion_bind_group = Self::create_motion_bind_group(
+            device,
+            &motion_bind_group_layout,
+            &params_buffer,
+            depth,
+        );
+        let resolve_bind_groups = Self::create_resolve_bind_groups(
+            device,
+            &resolve_bind_group_layout,
+            &params_buffer,
+            target,
+            &motion_target,
+            &history,
+        );
+
+        Self {
+            motion_target,
+            history,
+            fxaa_pipeline,
+            fxaa_bind_group_layout,
+            fxaa_bind_group,
+            motion_pipeline,
+            motion_bind_group_layout,
+            motion_bind_group,
+            resolve_pipeline,
+            resolve_bind_group_layout,
+            resolve_bind_groups,
+            params_buffer,
+            mode: AntiAliasing::None,
+            frame: 0,
+            prev_view_proj: Matrix4::identity(),
+            reset: true,
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        target: &texture::Texture,
+    ) -> (texture::Texture, [texture::Texture; 2]) {
+        let size = target.texture.size();
+        let create = |format, label| {
+            texture::Texture::create_color_target(
+                device,
+                size.width,
+                size.height,
+                format,
+                Some(label),
+            )
+        };
+
+        (
+            create(MOTION_FORMAT, "Motion Vectors"),
+            [
+                create(HDR_FORMAT, "TAA History 0"),
+                create(HDR_FORMAT, "TAA History 1"),
+            ],
+        )
+    }
+
+    fn create_fxaa_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        target: &texture::Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("FXAA Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&target.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&target.sampler),
+                },
+            ],
+        })
+    }
+
+    fn create_motion_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        params_buffer: &wgpu::Buffer,
+        depth: &texture::Texture,
+    ) -> Option<wgpu::BindGroup> {
+        if depth.texture.sample_count() != 1 {
+            return None;
+        }
+
+        Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("TAA Motion Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&depth.view),
+                },
+            ],
+        }))
+    }
+
+    fn create_resolve_bind_groups(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        params_buffer: &wgpu::Buffer,
+        target: &texture::Texture,
+        motion_target: &texture::Texture,
+        history: &[texture::Texture; 2],
+    ) -> [wgpu::BindGroup; 2] {
+        [0, 1].map(|i| {
+            let previous = &history[1 - i];
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("TAA Resolve Bind Group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&target.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&previous.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&motion_target.view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Sampler(&previous.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 4,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+            })
+        })
+    }
+
+    /// recreates the textures for a resized target or a new depth texture, called from the engine
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        target: &texture::Texture,
+        depth: &texture::Texture,
+    ) {
+        let (motion_target, history) = Self::create_targets(device, target);
+        self.motion_target = motion_target;
+        self.history = history;
+        self.fxaa_bind_group =
+            Self::create_fxaa_bind_group(device, &self.fxaa_bind_group_layout, target);
+        self.motion_bind_group = Self::create_motion_bind_group(
+            device,
+            &self.motion_bind_group_layout,
+            &self.params_buffer,
+            depth,
+        );
+        self.resolve_bind_groups = Self::create_resolve_bind_groups(
+            device,
+            &self.resolve_bind_group_layout,
+            &self.params_buffer,
+            target,
+            &self.motion_target,
+            &self.history,
+        );
+        self.reset = true;
+    }
+
+    /// Sets the projection jitter for this frame when TAA is on, and clears it otherwise.
+    /// Call before the camera uniform is updated from the projection
+    pub fn update(&mut self, queue: &wgpu::Queue, state: &RenderState, scene: &mut Scene) {
+        if state.anti_aliasing != self.mode {
+            self.reset = true;
+        }
+        self.mode = state.anti_aliasing;
+
+        if self.mode != AntiAliasing::Taa {
+            scene.projection.set_jitter(Vector2::zero());
+            return;
+        }
+
+        self.frame = self.frame.wrapping_add(1);
+        let size = self.motion_target.texture.size();
+        let index = self.frame % TAA_SAMPLES + 1;
+        scene.projection.set_jitter(Vector2::new(
+            (halton(index, 2) - 0.5) * 2.0 / size.width as f32,
+            (halton(index, 3) - 0.5) * 2.0 / size.height as f32,
+        ));
+
+        let view = scene.camera.calc_matrix();
+        let view_proj = scene.projection.calc_matrix() * view;
+        let unjittered_view_proj = scene.projection.calc_unjittered_matrix() * view;
+        if self.reset {
+            self.prev_view_proj = unjittered_view_proj;
+        }
+
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[TaaParams {
+                inv_view_proj: view_proj.invert().unwrap_or(Matrix4::identity()).into(),
+                unjittered_view_proj: unjittered_view_proj.into(),
+                prev_view_proj: self.prev_view_proj.into(),
+                blend: TAA_BLEND,
+                reset: self.reset as u32,
+                _padding: [0.0; 2],
+            }]),
+        );
+
+        self.prev_view_proj = unjittered_view_proj;
+        self.reset = false;
+    }
+
+    fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        pipeline: &wgpu::RenderPipeline,
+        bind_group: &wgpu::BindGroup,
+        output: &wgpu::TextureView,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Anti-aliases `target` in place. Call after the scene pass, before bloom and tonemapping
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &texture::Texture) {
+        let output = match self.mode {
+            AntiAliasing::None => return,
+            AntiAliasing::Fxaa => {
+                let output = &self.history[0];
+                self.draw(
+                    encoder,
+                    "FXAA Pass",
+                    &self.fxaa_pipeline,
+                    &self.fxaa_bind_group,
+                    &output.view,
+                );
+                output
+            }
+            AntiAliasing::Taa => {
+                let Some(motion_bind_group) = &self.motion_bind_group else {
+                    return;
+                };
+                self.draw(
+                    encoder,
+                    "TAA Motion Pass",
+                    &self.motion_pipeline,
+                    motion_bind_group,
+                    &self.motion_target.view,
+                );
+
+                let current = (self.frame % 2) as usize;
+                let output = &self.history[current];
+                self.draw(
+                    encoder,
+                    "TAA Resolve Pass",
+                    &self.resolve_pipeline,
+                    &self.resolve_bind_groups[current],
+                    &output.view,
+                );
+                output
+            }
+        };
+
+        encoder.copy_texture_to_texture(
+            output.texture.as_image_copy(),
+            target.texture.as_image_copy(),
+            target.texture.size(),
+        );
+    }
+}
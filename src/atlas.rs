@@ -0,0 +1,239 @@
+use anyhow::*;
+use std::collections::HashMap;
+
+use crate::sampler::SamplerManager;
+use crate::texture::{Texture, TextureOptions};
+
+/// Where an image ended up in the atlas
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasRect {
+    /// pixel position of the top left corner, without the padding
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// texture coordinates of the top left and bottom right corners
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+}
+
+/// Many small images packed into one texture, so they can be drawn with a single bind group
+pub struct TextureAtlas {
+    pub texture: Texture,
+    pub width: u32,
+    pub height: u32,
+    pub rects: HashMap<String, AtlasRect>,
+}
+
+impl TextureAtlas {
+    pub fn rect(&self, name: &str) -> Option<&AtlasRect> {
+        self.rects.get(name)
+    }
+
+    /// `[u_min, v_min, u_max, v_max]` of the image called `name`
+    pub fn uv_rect(&self, name: &str) -> Option<[f32; 4]> {
+        self.rects.get(name).map(|rect| {
+            [
+                rect.uv_min[0],
+                rect.uv_min[1],
+                rect.uv_max[0],
+                rect.uv_max[1],
+            ]
+        })
+    }
+}
+
+// top left corner of every padded image
+struct Packing {
+    width: u32,
+    height: u32,
+    positions: Vec<(u32, u32)>,
+}
+
+/// Collects named images and packs them into a `TextureAtlas`.
+///
+/// Images are placed on shelves, tallest first, in the smallest power of two size they fit in.
+/// Each image is surrounded by `padding` pixels repeating its border, so filtering and mipmaps
+/// don't pull in the neighbours.
+pub struct TextureAtlasBuilder {
+    images: Vec<(String, image::RgbaImage)>,
+    padding: u32,
+    max_size: u32,
+}
+
+impl Default for TextureAtlasBuilder {
+    fn default() -> Self {
+        Self {
+            images: vec![],
+            padding: 2,
+            max_size: 8192,
+        }
+    }
+}
+
+impl TextureAtlasBuilder {
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// largest width and height the atlas may grow to, usually `Limits::max_texture_dimension_2d`
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// adds an image, replacing an earlier one with the same name
+    pub fn add(&mut self, name: &str, img: &image::DynamicImage) {
+        self.images.retain(|(existing, _)| existing != name);
+        self.images.push((name.to_string(), img.to_rgba8()));
+    }
+
+    pub fn build(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        options: &TextureOptions,
+        samplers: &mut SamplerManager,
+        label: Option<&str>,
+    ) -> Result<TextureAtlas> {
+        if self.images.is_empty() {
+            bail!("texture atlas needs at least one image");
+        }
+        if let Some((name, _)) = self
+            .images
+            .iter()
+            .find(|(_, img)| img.width() == 0 || img.height() == 0)
+        {
+            bail!("atlas image {} is empty", name);
+        }
+
+        let sizes: Vec<(u32, u32)> = self
+            .images
+            .iter()
+            .map(|(_, img)| {
+                (
+                    img.width() + 2 * self.padding,
+                    img.height() + 2 * self.padding,
+                )
+            })
+            .collect();
+        let Packing {
+            width,
+            height,
+            positions,
+        } = self.pack(&sizes)?;
+
+        let mut canvas = image::RgbaImage::new(width, height);
+        for ((_, img), &(x, y)) in self.images.iter().zip(&positions) {
+            // writes the padding too, each padding pixel repeats the nearest border pixel
+            let (image_width, image_height) = img.dimensions();
+            for py in 0..image_height + 2 * self.padding {
+                for px in 0..image_width + 2 * self.padding {
+                    let source_x = px.saturating_sub(self.padding).min(image_width - 1);
+                    let source_y = py.saturating_sub(self.padding).min(image_height - 1);
+                    canvas.put_pixel(x + px, y + py, *img.get_pixel(source_x, source_y));
+                }
+            }
+        }
+
+        let rects = self
+            .images
+            .iter()
+            .zip(&positions)
+            .map(|((name, img), &(x, y))| {
+                let (x, y) = (x + self.padding, y + self.padding);
+                let rect = AtlasRect {
+                    x,
+                    y,
+                    width: img.width(),
+                    height: img.height(),
+                    uv_min: [x as f32 / width as f32, y as f32 / height as f32],
+                    uv_max: [
+                        (x + img.width()) as f32 / width as f32,
+                        (y + img.height()) as f32 / height as f32,
+                    ],
+                };
+                (name.clone(), rect)
+            })
+            .collect();
+
+        let texture = Texture::from_image_with_options(
+            device,
+            queue,
+            &image::DynamicImage::ImageRgba8(canvas),
+            options,
+            samplers,
+            label,
+        )?;
+
+        Ok(TextureAtlas {
+            texture,
+            width,
+            height,
+            rects,
+        })
+    }
+
+    fn pack(&self, sizes: &[(u32, u32)]) -> Result<Packing> {
+        let widest = sizes.iter().map(|size| size.0).max().unwrap_or(1);
+        let tallest = sizes.iter().map(|size| size.1).max().unwrap_or(1);
+        if widest > self.max_size || tallest > self.max_size {
+            bail!("an image doesn't fit in a {0}x{0} atlas", self.max_size);
+        }
+
+        let area: u64 = sizes.iter().map(|&(w, h)| w as u64 * h as u64).sum();
+        let side = ((area as f64).sqrt() as u32).max(1).next_power_of_two();
+        let mut width = side.max(widest.next_power_of_two()).min(self.max_size);
+        let mut height = side.max(tallest.next_power_of_two()).min(self.max_size);
+
+        let mut order: Vec<usize> = (0..sizes.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(sizes[i].1));
+
+        loop {
+            if let Some(positions) = Self::pack_shelves(sizes, &order, width, height) {
+                return Ok(Packing {
+                    width,
+                    height,
+                    positions,
+                });
+            }
+
+            if width <= height && width < self.max_size {
+                width = (width * 2).min(self.max_size);
+            } else if height < self.max_size {
+                height = (height * 2).min(self.max_size);
+            } else {
+                bail!("images don't fit in a {0}x{0} atlas", self.max_size);
+            }
+        }
+    }
+
+    fn pack_shelves(
+        sizes: &[(u32, u32)],
+        order: &[usize],
+        width: u32,
+        height: u32,
+    ) -> Option<Vec<(u32, u32)>> {
+        let mut positions = vec![(0, 0); sizes.len()];
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+
+        for &i in order {
+            let (w, h) = sizes[i];
+            if x + w > width {
+                y += shelf_height;
+                x = 0;
+                shelf_height = 0;
+            }
+            if y + h > height {
+                return None;
+            }
+
+            positions[i] = (x, y);
+            x += w;
+            shelf_height = shelf_height.max(h);
+        }
+
+        Some(positions)
+    }
+}
@@ -0,0 +1,413 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::hdr::HDR_FORMAT;
+use crate::renderer::RenderState;
+use crate::texture;
+
+const MAX_BLOOM_MIPS: u32 = 7;
+// stop the chain before mips get smaller than this
+const MIN_BLOOM_MIP_SIZE: u32 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BloomComposite {
+    /// `mix(scene, bloom, intensity)`, doesn't add light to the scene. Meant to be used without a threshold
+    EnergyConserving,
+    /// `scene + bloom * intensity`, usually combined with a threshold
+    Additive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BloomSettings {
+    pub enabled: bool,
+    pub intensity: f32,
+    /// upsample filter radius in texels of each mip, larger values spread the glow further
+    pub radius: f32,
+    /// only parts brighter than this bloom, 0 disables the threshold
+    pub threshold: f32,
+    /// softness of the threshold, 0 is a hard cut and 1 fades in from 0
+    pub knee: f32,
+    pub composite: BloomComposite,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            intensity: 0.04,
+            radius: 1.0,
+            threshold: 0.0,
+            knee: 0.5,
+            composite: BloomComposite::EnergyConserving,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct BloomParams {
+    threshold: f32,
+    knee: f32,
+    radius: f32,
+    _padding: f32,
+}
+
+/// Bloom on the HDR target.
+///
+/// The target is downsampled into a half resolution mip chain (thresholded and Karis averaged on
+/// the first step), the chain is upsampled back with a tent filter adding each level to the one
+/// above, and the result is blended into the target before tonemapping.
+pub struct BloomPass {
+    mip_views: Vec<wgpu::TextureView>,
+    sampler: wgpu::Sampler,
+    bind_group_layout: wgpu::BindGroupLayout,
+    params_buffer: wgpu::Buffer,
+    downsample_first_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    mix_pipeline: wgpu::RenderPipeline,
+    additive_pipeline: wgpu::RenderPipeline,
+    // bind_groups[0] samples the HDR target, bind_groups[i] samples mip i - 1
+    bind_groups: Vec<wgpu::BindGroup>,
+    settings: BloomSettings,
+}
+
+impl BloomPass {
+    /// `target` is the HDR scene target the bloom reads from and is composited into
+    pub fn new(device: &wgpu::Device, target: &texture::Texture) -> Self {
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Bloom Params"),
+            size: std::mem::size_of::<BloomParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Bloom Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Bloom Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("shaders/fullscreen.wgsl"),
+                    include_str!("shaders/bloom.wgsl")
+                )
+                .into(),
+            ),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bloom Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        // the blend constant is set to the intensity when drawing
+        let mix = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Constant,
+            dst_factor: wgpu::BlendFactor::OneMinusConstant,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let additive_constant = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::Constant,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+
+        let create_pipeline =
+            |label: &str, entry_point: &str, blend: Option<wgpu::BlendComponent>| {
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_fullscreen",
+                        buffers: &[],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point,
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: HDR_FORMAT,
+                            blend: blend.map(|component| wgpu::BlendState {
+                                color: component,
+                                alpha: wgpu::BlendComponent::REPLACE,
+                            }),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: wgpu::PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState::default(),
+                    multiview: None,
+                })
+            };
+
+        let downsample_first_pipeline = create_pipeline(
+            "Bloom Downsample First Pipeline",
+            "fs_downsample_first",
+            None,
+        );
+        let downsample_pipeline =
+            create_pipeline("Bloom Downsample Pipeline", "fs_downsample", None);
+        let upsample_pipeline =
+            create_pipeline("Bloom Upsample Pipeline", "fs_upsample", Some(additive));
+        let mix_pipeline = create_pipeline("Bloom Mix Pipeline", "fs_composite", Some(mix));
+        let additive_pipeline = create_pipeline(
+            "Bloom Additive Pipeline",
+            "fs_composite",
+            Some(additive_constant),
+        );
+
+        let mip_views = Self::create_mip_chain(device, target);
+        let bind_groups = Self::create_bind_groups(
+            device,
+            &bind_group_layout,
+            &sampler,
+            &params_buffer,
+            target,
+            &mip_views,
+        );
+
+        Self {
+            mip_views,
+            sampler,
+            bind_group_layout,
+            params_buffer,
+            downsample_first_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            mix_pipeline,
+            additive_pipeline,
+            bind_groups,
+            settings: Default::default(),
+        }
+    }
+
+    // the views keep the texture alive
+    fn create_mip_chain(
+        device: &wgpu::Device,
+        target: &texture::Texture,
+    ) -> Vec<wgpu::TextureView> {
+        let size = target.texture.size();
+        let width = (size.width / 2).max(1);
+        let height = (size.height / 2).max(1);
+
+        let mut mip_count = 1;
+        while mip_count < MAX_BLOOM_MIPS && (width.min(height) >> mip_count) >= MIN_BLOOM_MIP_SIZE {
+            mip_count += 1;
+        }
+
+        let mip_chain = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Bloom Mip Chain"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: mip_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        (0..mip_count)
+            .map(|mip| {
+                mip_chain.create_view(&wgpu::TextureViewDescriptor {
+                    base_mip_level: mip,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect()
+    }
+
+    fn create_bind_groups(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        params_buffer: &wgpu::Buffer,
+        target: &texture::Texture,
+        mip_views: &[wgpu::TextureView],
+    ) -> Vec<wgpu::BindGroup> {
+        std::iter::once(&target.view)
+            .chain(mip_views.iter())
+            .map(|view| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Bloom Bind Group"),
+                    layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: params_buffer.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect()
+    }
+
+    /// recreates the mip chain for the resized HDR target, call after `HdrPass::resize`
+    pub fn resize(&mut self, device: &wgpu::Device, target: &texture::Texture) {
+        self.mip_views = Self::create_mip_chain(device, target);
+        self.bind_groups = Self::create_bind_groups(
+            device,
+            &self.bind_group_layout,
+            &self.sampler,
+            &self.params_buffer,
+            target,
+            &self.mip_views,
+        );
+    }
+
+    pub fn update(&mut self, queue: &wgpu::Queue, state: &RenderState) {
+        self.settings = state.bloom;
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            bytemuck::cast_slice(&[BloomParams {
+                threshold: state.bloom.threshold.max(0.0),
+                knee: state.bloom.knee.clamp(0.0, 1.0),
+                radius: state.bloom.radius.max(0.0),
+                _padding: 0.0,
+            }]),
+        );
+    }
+
+    fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        pipeline: &wgpu::RenderPipeline,
+        bind_group: &wgpu::BindGroup,
+        output: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        let intensity = self.settings.intensity.max(0.0) as f64;
+        render_pass.set_blend_constant(wgpu::Color {
+            r: intensity,
+            g: intensity,
+            b: intensity,
+            a: intensity,
+        });
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// Blooms `target` in place, does nothing if bloom is disabled in `RenderState`.
+    /// Call after the scene has been rendered and before `HdrPass::render`.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &texture::Texture) {
+        if !self.settings.enabled {
+            return;
+        }
+
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        for (mip, view) in self.mip_views.iter().enumerate() {
+            let pipeline = if mip == 0 {
+                &self.downsample_first_pipeline
+            } else {
+                &self.downsample_pipeline
+            };
+            self.draw(
+                encoder,
+                "Bloom Downsample",
+                pipeline,
+                &self.bind_groups[mip],
+                view,
+                clear,
+            );
+        }
+
+        for mip in (1..self.mip_views.len()).rev() {
+            self.draw(
+                encoder,
+                "Bloom Upsample",
+                &self.upsample_pipeline,
+                &self.bind_groups[mip + 1],
+                &self.mip_views[mip - 1],
+                wgpu::LoadOp::Load,
+            );
+        }
+
+        let composite_pipeline = match self.settings.composite {
+            BloomComposite::EnergyConserving => &self.mix_pipeline,
+            BloomComposite::Additive => &self.additive_pipeline,
+        };
+        self.draw(
+            encoder,
+            "Bloom Composite",
+            composite_pipeline,
+            &self.bind_groups[1],
+            &target.view,
+            wgpu::LoadOp::Load,
+        );
+    }
+}
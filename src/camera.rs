@@ -1,13 +1,15 @@
 use bytemuck::{Pod, Zeroable};
 use cgmath::*;
 use std::f32::consts::FRAC_PI_2;
-use winit::event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
-use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::event::WindowEvent;
 
+use crate::culling::Frustum;
 use crate::engine::Engine;
+use crate::input_map::{self, Binding};
+use crate::orbit::{ArcballController, OrbitController};
 
 #[rustfmt::skip]
-const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
+pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0,
     0.0, 0.0, 0.5, 0.5,
@@ -16,11 +18,38 @@ const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
 
 pub const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
 
+/// How an orthographic projection keeps its extent when the viewport changes size
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OrthographicScaling {
+    /// `height` world units fit vertically, the width follows the aspect ratio
+    FixedHeight(f32),
+    /// `width` world units fit horizontally, the height follows the aspect ratio
+    FixedWidth(f32),
+    /// one world unit covers this many pixels, a bigger window shows more of the world
+    PixelsPerUnit(f32),
+    /// explicit view space bounds, stretched when the aspect ratio changes
+    Bounds {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProjectionKind {
+    Perspective { fovy: Rad<f32> },
+    Orthographic { scaling: OrthographicScaling },
+}
+
 pub struct Projection {
-    aspect: f32,
-    fovy: Rad<f32>,
+    kind: ProjectionKind,
+    width: f32,
+    height: f32,
     znear: f32,
     zfar: f32,
+    // sub pixel offset in NDC, used by TAA
+    jitter: Vector2<f32>,
 }
 
 impl Default for Projection {
@@ -31,20 +60,121 @@ impl Default for Projection {
 
 impl Projection {
     pub fn new<F: Into<Rad<f32>>>(width: u32, height: u32, fovy: F, znear: f32, zfar: f32) -> Self {
+        Self::with_kind(
+            width,
+            height,
+            ProjectionKind::Perspective { fovy: fovy.into() },
+            znear,
+            zfar,
+        )
+    }
+
+    pub fn orthographic(
+        width: u32,
+        height: u32,
+        scaling: OrthographicScaling,
+        znear: f32,
+        zfar: f32,
+    ) -> Self {
+        Self::with_kind(
+            width,
+            height,
+            ProjectionKind::Orthographic { scaling },
+            znear,
+            zfar,
+        )
+    }
+
+    pub fn with_kind(width: u32, height: u32, kind: ProjectionKind, znear: f32, zfar: f32) -> Self {
         Self {
-            aspect: width as f32 / height as f32,
-            fovy: fovy.into(),
+            kind,
+            width: width.max(1) as f32,
+            height: height.max(1) as f32,
             znear,
             zfar,
+            jitter: Vector2::zero(),
         }
     }
 
+    /// keeps the kind and its scaling policy, only the viewport size changes
     pub fn resize(&mut self, width: u32, height: u32) {
-        self.aspect = width as f32 / height as f32;
+        self.width = width.max(1) as f32;
+        self.height = height.max(1) as f32;
+    }
+
+    pub fn kind(&self) -> ProjectionKind {
+        self.kind
     }
 
+    pub fn set_kind(&mut self, kind: ProjectionKind) {
+        self.kind = kind;
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.width / self.height
+    }
+
+    /// `(left, right, bottom, top)` of the view volume for orthographic projections
+    pub fn orthographic_bounds(&self) -> Option<(f32, f32, f32, f32)> {
+        let ProjectionKind::Orthographic { scaling } = self.kind else {
+            return None;
+        };
+
+        let (half_width, half_height) = match scaling {
+            OrthographicScaling::FixedHeight(height) => {
+                (height * 0.5 * self.aspect(), height * 0.5)
+            }
+            OrthographicScaling::FixedWidth(width) => (width * 0.5, width * 0.5 / self.aspect()),
+            OrthographicScaling::PixelsPerUnit(pixels) => {
+                (self.width * 0.5 / pixels, self.height * 0.5 / pixels)
+            }
+            OrthographicScaling::Bounds {
+                left,
+                right,
+                bottom,
+                top,
+            } => return Some((left, right, bottom, top)),
+        };
+        Some((-half_width, half_width, -half_height, half_height))
+    }
+
+    /// projection including the jitter set with `set_jitter`
     pub fn calc_matrix(&self) -> Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX * perspective(self.fovy, self.aspect, self.znear, self.zfar)
+        Matrix4::from_translation(self.jitter.extend(0.0)) * self.calc_unjittered_matrix()
+    }
+
+    pub fn calc_unjittered_matrix(&self) -> Matrix4<f32> {
+        self.calc_matrix_with_depth(self.znear, self.zfar)
+    }
+
+    /// shifts the projection by `jitter` in NDC units, e.g. `2.0 / width` moves it by one pixel
+    pub fn set_jitter(&mut self, jitter: Vector2<f32>) {
+        self.jitter = jitter;
+    }
+
+    pub fn jitter(&self) -> Vector2<f32> {
+        self.jitter
+    }
+
+    /// same projection clipped to another depth range, e.g. to slice the frustum into shadow cascades
+    pub fn calc_matrix_with_depth(&self, znear: f32, zfar: f32) -> Matrix4<f32> {
+        match self.kind {
+            ProjectionKind::Perspective { fovy } => {
+                OPENGL_TO_WGPU_MATRIX * perspective(fovy, self.aspect(), znear, zfar)
+            }
+            ProjectionKind::Orthographic { .. } => {
+                let (left, right, bottom, top) = self.orthographic_bounds().unwrap();
+                OPENGL_TO_WGPU_MATRIX * ortho(left, right, bottom, top, znear, zfar)
+            }
+        }
+    }
+
+    pub fn znear(&self) -> f32 {
+        self.znear
+    }
+
+    pub fn zfar(&self) -> f32 {
+        self.zfar
     }
 }
 
@@ -52,6 +182,8 @@ pub struct Camera {
     pub position: Point3<f32>,
     yaw: Rad<f32>,
     pitch: Rad<f32>,
+    // tilted away from +Y by the arcball controller
+    up: Vector3<f32>,
 }
 
 impl Default for Camera {
@@ -64,6 +196,7 @@ impl Default for Camera {
             },
             yaw: Rad(0.0),
             pitch: Rad(0.0),
+            up: Vector3::unit_y(),
         }
     }
 }
@@ -78,14 +211,44 @@ impl Camera {
             position: position.into(),
             yaw: yaw.into(),
             pitch: pitch.into(),
+            up: Vector3::unit_y(),
         }
     }
 
-    pub fn calc_matrix(&self) -> Matrix4<f32> {
+    pub fn yaw(&self) -> Rad<f32> {
+        self.yaw
+    }
+
+    pub fn pitch(&self) -> Rad<f32> {
+        self.pitch
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.up
+    }
+
+    /// points the camera along `forward`, `up` doesn't have to be exactly perpendicular to it
+    pub fn set_orientation(&mut self, forward: Vector3<f32>, up: Vector3<f32>) {
+        let forward = forward.normalize();
+        self.yaw = Rad(forward.z.atan2(forward.x));
+        self.pitch = Rad(forward.y.clamp(-1.0, 1.0).asin());
+        self.up = up.normalize();
+    }
+
+    /// turns towards `target`, keeping +Y up
+    pub fn look_at(&mut self, target: Point3<f32>) {
+        self.set_orientation(target - self.position, Vector3::unit_y());
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
         let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
 
-        let center = Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize();
+        Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize()
+    }
+
+    pub fn calc_matrix(&self) -> Matrix4<f32> {
+        let center = self.forward();
         Matrix4::look_at_rh(
             self.position,
             Point3::new(
@@ -94,124 +257,297 @@ impl Camera {
                 center.z + self.position.z,
             ),
             // Point3::new(center.x, center.y, center.z),
-            Vector3::unit_y(),
+            self.up,
         )
     }
 }
 
-pub struct CameraController {
+// mouse look rotation per pixel at sensitivity 1
+const RADIANS_PER_PIXEL: f32 = 0.005;
+
+/// fraction of the way an exponential smoothing with time constant `smoothing` seconds covers in `dt`
+fn smoothing_factor(smoothing: f32, dt: f32) -> f32 {
+    if smoothing > 0.0 {
+        1.0 - (-dt / smoothing).exp()
+    } else {
+        1.0
+    }
+}
+
+/// WASD fly camera, the mouse turns it while the left button is held or the cursor is grabbed
+///
+/// Every field is a setting that can be changed while running. Rotation and translation are
+/// smoothed exponentially, the smoothing is the time constant in seconds (0 follows the input
+/// directly). On top of that the velocity ramps up and down with `acceleration` and `deceleration`.
+pub struct FlyController {
+    /// units per second
+    pub speed: f32,
+    pub sensitivity: f32,
+    /// speed multiplier while `MOVE_FAST` is held
+    pub fast_multiplier: f32,
+    /// speed multiplier while `MOVE_SLOW` is held
+    pub slow_multiplier: f32,
+    /// speed gained per second in multiples of the top speed, `f32::INFINITY` starts instantly
+    pub acceleration: f32,
+    /// speed lost per second in multiples of `speed` (or of the current speed when faster than that),
+    /// `f32::INFINITY` stops instantly
+    pub deceleration: f32,
+    pub rotation_smoothing: f32,
+    pub translation_smoothing: f32,
+    /// scrolling changes `speed` by `speed_step` per line instead of moving forward and back
+    pub scroll_adjusts_speed: bool,
+    pub speed_step: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
     amount_left: f32,
     amount_right: f32,
     amount_forward: f32,
     amount_backward: f32,
     amount_up: f32,
     amount_down: f32,
-    rotate_horizontal: f32,
-    rotate_vertical: f32,
+    fast: bool,
+    slow: bool,
+    rotating: bool,
+    // pixels dragged that the smoothing hasn't applied yet
+    rotation: Vector2<f32>,
+    // scroll lines since the last update
     scroll: f32,
-    sensitivity: f32,
-    speed: f32,
+    // after acceleration, and after smoothing on top of that
+    ramped_velocity: Vector3<f32>,
+    velocity: Vector3<f32>,
 }
 
-impl Default for CameraController {
+impl Default for FlyController {
     fn default() -> Self {
-        CameraController::new(3.0, 1.0)
+        FlyController::new(3.0, 1.0)
     }
 }
 
-impl CameraController {
+impl FlyController {
     pub fn new(speed: f32, sensitivity: f32) -> Self {
         Self {
+            speed,
+            sensitivity,
+            fast_multiplier: 3.0,
+            slow_multiplier: 0.25,
+            acceleration: 8.0,
+            deceleration: 10.0,
+            rotation_smoothing: 0.03,
+            translation_smoothing: 0.0,
+            scroll_adjusts_speed: false,
+            speed_step: 1.2,
+            min_speed: 0.1,
+            max_speed: 100.0,
             amount_left: 0.0,
             amount_right: 0.0,
             amount_forward: 0.0,
             amount_backward: 0.0,
             amount_up: 0.0,
             amount_down: 0.0,
-            rotate_horizontal: 0.0,
-            rotate_vertical: 0.0,
+            fast: false,
+            slow: false,
+            rotating: false,
+            rotation: Vector2::zero(),
             scroll: 0.0,
-            sensitivity,
-            speed,
+            ramped_velocity: Vector3::zero(),
+            velocity: Vector3::zero(),
         }
     }
 
-    pub fn process_keyboard(&mut self, key: KeyCode, state: ElementState) -> bool {
-        let amount = if state == ElementState::Pressed {
-            1.0
-        } else {
-            0.0
-        };
-        match key {
-            KeyCode::KeyW => {
-                self.amount_forward = amount;
-                true
+    pub fn process_action(&mut self, action: &str, pressed: bool) -> bool {
+        let amount = if pressed { 1.0 } else { 0.0 };
+        let target = match action {
+            input_map::MOVE_FORWARD => &mut self.amount_forward,
+            input_map::MOVE_BACKWARD => &mut self.amount_backward,
+            input_map::MOVE_LEFT => &mut self.amount_left,
+            input_map::MOVE_RIGHT => &mut self.amount_right,
+            input_map::MOVE_UP => &mut self.amount_up,
+            input_map::MOVE_DOWN => &mut self.amount_down,
+            input_map::MOVE_FAST => {
+                self.fast = pressed;
+                return true;
             }
-            KeyCode::KeyS => {
-                self.amount_backward = amount;
-                true
+            input_map::MOVE_SLOW => {
+                self.slow = pressed;
+                return true;
             }
-            KeyCode::KeyA => {
-                self.amount_left = amount;
-                true
+            input_map::ROTATE_CAMERA => {
+                self.rotating = pressed;
+                return true;
             }
-            KeyCode::KeyD => {
-                self.amount_right = amount;
-                true
-            }
-            KeyCode::Space => {
-                self.amount_up = amount;
-                true
-            }
-            KeyCode::ShiftLeft => {
-                self.amount_down = amount;
+            _ => return false,
+        };
+        *target = amount;
+        true
+    }
+
+    pub fn dragging(&self) -> bool {
+        self.rotating
+    }
+
+    pub fn release_all(&mut self) {
+        self.amount_left = 0.0;
+        self.amount_right = 0.0;
+        self.amount_forward = 0.0;
+        self.amount_backward = 0.0;
+        self.amount_up = 0.0;
+        self.amount_down = 0.0;
+        self.fast = false;
+        self.slow = false;
+        self.rotating = false;
+    }
+
+    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        self.rotation += Vector2::new(mouse_dx as f32, mouse_dy as f32);
+    }
+
+    pub fn process_axis(&mut self, axis: &str, value: f32) -> bool {
+        match axis {
+            input_map::ZOOM => {
+                self.scroll += value;
                 true
             }
             _ => false,
         }
     }
 
-    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
-        self.rotate_horizontal = mouse_dx as f32;
-        self.rotate_vertical = mouse_dy as f32;
+    /// current velocity in world units per second
+    pub fn velocity(&self) -> Vector3<f32> {
+        self.velocity
     }
 
-    pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
-        self.scroll = -match delta {
-            MouseScrollDelta::LineDelta(_, scroll) => scroll * 100.0,
-            MouseScrollDelta::PixelDelta(winit::dpi::PhysicalPosition { y: scroll, .. }) => {
-                *scroll as f32
-            }
-        };
+    /// `speed` with the fast / slow modifiers applied
+    pub fn top_speed(&self) -> f32 {
+        let mut speed = self.speed;
+        if self.fast {
+            speed *= self.fast_multiplier;
+        }
+        if self.slow {
+            speed *= self.slow_multiplier;
+        }
+        speed
     }
 
     pub fn update_camera(&mut self, camera: &mut Camera, dt: instant::Duration) {
         let dt = dt.as_secs_f32();
 
+        let rotation = self.rotation * smoothing_factor(self.rotation_smoothing, dt);
+        self.rotation -= rotation;
+        let angle = RADIANS_PER_PIXEL * self.sensitivity;
+        camera.up = Vector3::unit_y();
+        camera.yaw += Rad(rotation.x * angle);
+        camera.pitch =
+            Rad((camera.pitch.0 - rotation.y * angle).clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2));
+
+        let scroll = std::mem::take(&mut self.scroll);
+        if self.scroll_adjusts_speed {
+            self.speed =
+                (self.speed * self.speed_step.powf(scroll)).clamp(self.min_speed, self.max_speed);
+        } else {
+            // a scroll line moves as far as 100 pixels of a touchpad
+            camera.position +=
+                camera.forward() * -scroll * 100.0 * self.speed * self.sensitivity * dt;
+        }
+
         let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
-        let forward = Vector3::new(yaw_cos, 0.0, yaw_sin).normalize();
-        let right = Vector3::new(-yaw_sin, 0.0, yaw_cos).normalize();
-        camera.position += forward * (self.amount_forward - self.amount_backward) * self.speed * dt;
-        camera.position += right * (self.amount_right - self.amount_left) * self.speed * dt;
+        let forward = Vector3::new(yaw_cos, 0.0, yaw_sin);
+        let right = Vector3::new(-yaw_sin, 0.0, yaw_cos);
+        let mut direction = forward * (self.amount_forward - self.amount_backward)
+            + right * (self.amount_right - self.amount_left)
+            + Vector3::unit_y() * (self.amount_up - self.amount_down);
+        // diagonals aren't faster
+        if direction.magnitude2() > 1.0 {
+            direction = direction.normalize();
+        }
 
-        let (pitch_sin, pitch_cos) = camera.pitch.0.sin_cos();
-        let scrollward =
-            Vector3::new(pitch_cos * yaw_cos, pitch_sin, pitch_cos * yaw_sin).normalize();
-        camera.position += scrollward * self.scroll * self.speed * self.sensitivity * dt;
-        self.scroll = 0.0;
+        let top_speed = self.top_speed();
+        let target = direction * top_speed;
+        let change = target - self.ramped_velocity;
+        let max_change = if target.magnitude2() >= self.ramped_velocity.magnitude2() {
+            self.acceleration * top_speed * dt
+        } else {
+            self.deceleration * self.ramped_velocity.magnitude().max(self.speed) * dt
+        };
+        let distance = change.magnitude();
+        self.ramped_velocity = if distance == 0.0 || distance <= max_change {
+            target
+        } else {
+            self.ramped_velocity + change / distance * max_change
+        };
 
-        camera.position.y += (self.amount_up - self.amount_down) * self.speed * dt;
+        self.velocity += (self.ramped_velocity - self.velocity)
+            * smoothing_factor(self.translation_smoothing, dt);
+        camera.position += self.velocity * dt;
+    }
+}
 
-        camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
-        camera.pitch += Rad(-self.rotate_vertical) * self.sensitivity * dt;
+/// The controller `camera::input` and the engine feed, pick one per scene
+pub enum CameraController {
+    Fly(FlyController),
+    Orbit(OrbitController),
+    Arcball(ArcballController),
+}
 
-        self.rotate_horizontal = 0.0;
-        self.rotate_vertical = 0.0;
+impl Default for CameraController {
+    fn default() -> Self {
+        CameraController::Fly(Default::default())
+    }
+}
+
+impl CameraController {
+    /// fly controller, kept for scenes written before there was a choice
+    pub fn new(speed: f32, sensitivity: f32) -> Self {
+        CameraController::Fly(FlyController::new(speed, sensitivity))
+    }
+
+    /// reacts to an action from the input map, `false` if the controller doesn't use it
+    pub fn process_action(&mut self, action: &str, pressed: bool) -> bool {
+        match self {
+            CameraController::Fly(controller) => controller.process_action(action, pressed),
+            CameraController::Orbit(controller) => controller.process_action(action, pressed),
+            CameraController::Arcball(controller) => controller.process_action(action, pressed),
+        }
+    }
+
+    /// whether a button the controller drags with is held, mouse motion is only passed on then
+    pub fn dragging(&self) -> bool {
+        match self {
+            CameraController::Fly(controller) => controller.dragging(),
+            CameraController::Orbit(controller) => controller.dragging(),
+            CameraController::Arcball(controller) => controller.dragging(),
+        }
+    }
+
+    /// lets go of every held action, the release events never arrive once the window lost focus
+    pub fn release_all(&mut self) {
+        match self {
+            CameraController::Fly(controller) => controller.release_all(),
+            CameraController::Orbit(controller) => controller.release_all(),
+            CameraController::Arcball(controller) => controller.release_all(),
+        }
+    }
+
+    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        match self {
+            CameraController::Fly(controller) => controller.process_mouse(mouse_dx, mouse_dy),
+            CameraController::Orbit(controller) => controller.process_mouse(mouse_dx, mouse_dy),
+            CameraController::Arcball(controller) => controller.process_mouse(mouse_dx, mouse_dy),
+        }
+    }
 
-        if camera.pitch < -Rad(SAFE_FRAC_PI_2) {
-            camera.pitch = -Rad(SAFE_FRAC_PI_2);
-        } else if camera.pitch > Rad(SAFE_FRAC_PI_2) {
-            camera.pitch = Rad(SAFE_FRAC_PI_2);
+    pub fn process_axis(&mut self, axis: &str, value: f32) -> bool {
+        match self {
+            CameraController::Fly(controller) => controller.process_axis(axis, value),
+            CameraController::Orbit(controller) => controller.process_axis(axis, value),
+            CameraController::Arcball(controller) => controller.process_axis(axis, value),
+        }
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera, dt: instant::Duration) {
+        match self {
+            CameraController::Fly(controller) => controller.update_camera(camera, dt),
+            CameraController::Orbit(controller) => controller.update_camera(camera, dt),
+            CameraController::Arcball(controller) => controller.update_camera(camera, dt),
         }
     }
 }
@@ -238,34 +574,57 @@ impl CameraUniform {
     pub fn update_view_proj(&mut self, camera: &Camera, projection: &Projection) {
         self.view_proj = (projection.calc_matrix() * camera.calc_matrix()).into();
     }
+
+    pub fn view_proj(&self) -> Matrix4<f32> {
+        self.view_proj.into()
+    }
+
+    /// what the camera saw at the last `update_view_proj`
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.view_proj())
+    }
 }
 
+/// Feeds the camera controller the actions and axes `engine.input_map` maps `event` to
 pub fn input(engine: &mut Engine, event: &WindowEvent) -> bool {
-    match event {
-        WindowEvent::KeyboardInput {
-            event:
-                KeyEvent {
-                    physical_key: PhysicalKey::Code(key),
-                    state,
-                    ..
-                },
-            ..
-        } => engine
-            .scene
-            .camera_controller
-            .process_keyboard(*key, *state),
+    let controller = &mut engine.scene.camera_controller;
+    let input_map = &engine.input_map;
+
+    let handled = match event {
         WindowEvent::MouseWheel { delta, .. } => {
-            engine.scene.camera_controller.process_scroll(delta);
-            true
-        }
-        WindowEvent::MouseInput {
-            button: MouseButton::Left,
-            state,
-            ..
-        } => {
-            engine.mouse_pressed = *state == ElementState::Pressed;
-            true
+            let lines = input_map::wheel_lines(delta);
+            let binding = if lines > 0.0 {
+                Binding::WheelUp
+            } else {
+                Binding::WheelDown
+            };
+            let mut handled = false;
+            for (axis, scale) in input_map.axes_for(binding) {
+                handled |= controller.process_axis(axis, lines.abs() * scale);
+            }
+            for action in input_map.actions_for(binding) {
+                handled |= controller.process_action(action, true);
+                controller.process_action(action, false);
+            }
+            handled
         }
-        _ => false,
-    }
+        _ => match Binding::from_event(event) {
+            Some((binding, pressed)) => {
+                let mut handled = false;
+                for action in input_map.actions_for(binding) {
+                    handled |= controller.process_action(action, pressed);
+                }
+                if pressed {
+                    for (axis, scale) in input_map.axes_for(binding) {
+                        handled |= controller.process_axis(axis, scale);
+                    }
+                }
+                handled
+            }
+            None => false,
+        },
+    };
+
+    engine.mouse_pressed = engine.scene.camera_controller.dragging();
+    handled
 }
@@ -0,0 +1,279 @@
+use anyhow::*;
+use cgmath::*;
+use std::fmt;
+use std::path::Path;
+
+use crate::camera::{Camera, SAFE_FRAC_PI_2};
+
+/// A recorded camera pose, `time` in seconds from the start of the path
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    pub time: f32,
+    pub position: Point3<f32>,
+    pub yaw: Rad<f32>,
+    /// within `SAFE_FRAC_PI_2` of level, looking straight up or down leaves no right vector
+    pub pitch: Rad<f32>,
+}
+
+fn clamp_pitch(pitch: Rad<f32>) -> Rad<f32> {
+    Rad(pitch.0.clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2))
+}
+
+impl Keyframe {
+    pub fn from_camera(camera: &Camera, time: f32) -> Self {
+        Self {
+            time,
+            position: camera.position,
+            yaw: camera.yaw(),
+            pitch: clamp_pitch(camera.pitch()),
+        }
+    }
+
+    /// camera to world rotation without roll, the camera looks down its -Z
+    pub fn rotation(&self) -> Quaternion<f32> {
+        let (sin_pitch, cos_pitch) = clamp_pitch(self.pitch).0.sin_cos();
+        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+        let forward = Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw);
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+        Quaternion::from(Matrix3::from_cols(right, up, -forward))
+    }
+}
+
+/// Camera keyframes sorted by time.
+///
+/// Positions follow a Catmull-Rom spline through the keyframes, with the tangents scaled by the
+/// time between them so the speed doesn't jump where keyframes are spaced unevenly. Orientations
+/// are slerped between neighbouring keyframes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// inserts `keyframe` in time order, replacing one at the same time
+    pub fn add_keyframe(&mut self, keyframe: Keyframe) {
+        match self
+            .keyframes
+            .binary_search_by(|item| item.time.total_cmp(&keyframe.time))
+        {
+            std::result::Result::Ok(index) => self.keyframes[index] = keyframe,
+            std::result::Result::Err(index) => self.keyframes.insert(index, keyframe),
+        }
+    }
+
+    /// adds the camera's pose `gap` seconds after the last keyframe, or at 0 on an empty path
+    pub fn record(&mut self, camera: &Camera, gap: f32) {
+        let time = self.keyframes.last().map_or(0.0, |last| last.time + gap);
+        self.add_keyframe(Keyframe::from_camera(camera, time));
+    }
+
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+    }
+
+    /// time of the last keyframe
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |last| last.time)
+    }
+
+    // velocity at keyframe `i` from its neighbours, one sided at the ends
+    fn tangent(&self, i: usize) -> Vector3<f32> {
+        let before = &self.keyframes[i.saturating_sub(1)];
+        let after = &self.keyframes[(i + 1).min(self.keyframes.len() - 1)];
+        let span = after.time - before.time;
+        if span > 0.0 {
+            (after.position - before.position) / span
+        } else {
+            Vector3::zero()
+        }
+    }
+
+    /// position and camera to world rotation at `time`, clamped to the ends of the path
+    pub fn sample(&self, time: f32) -> Option<(Point3<f32>, Quaternion<f32>)> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        if time <= first.time {
+            return Some((first.position, first.rotation()));
+        }
+        if time >= last.time {
+            return Some((last.position, last.rotation()));
+        }
+
+        // the keyframe after `time`, the one before it is at least the first
+        let next = self.keyframes.partition_point(|item| item.time <= time);
+        let (a, b) = (&self.keyframes[next - 1], &self.keyframes[next]);
+        let span = b.time - a.time;
+        let t = (time - a.time) / span;
+
+        // cubic Hermite with the Catmull-Rom tangents
+        let (t2, t3) = (t * t, t * t * t);
+        let h00 = 2.0 * t3 - 3.0 * t2 + 1.0;
+        let h10 = t3 - 2.0 * t2 + t;
+        let h01 = -2.0 * t3 + 3.0 * t2;
+        let h11 = t3 - t2;
+        let position = a.position * h00
+            + self.tangent(next - 1) * span * h10
+            + b.position.to_vec() * h01
+            + self.tangent(next) * span * h11;
+
+        let (from, mut to) = (a.rotation(), b.rotation());
+        // the short way around
+        if from.dot(to) < 0.0 {
+            to = -to;
+        }
+        Some((position, from.slerp(to, t)))
+    }
+
+    /// puts `camera` where the path is at `time`, `false` if the path is empty
+    pub fn apply(&self, camera: &mut Camera, time: f32) -> bool {
+        let Some((position, rotation)) = self.sample(time) else {
+            return false;
+        };
+        camera.position = position;
+        camera.set_orientation(rotation * -Vector3::unit_z(), Vector3::unit_y());
+        true
+    }
+
+    /// one keyframe per line, `<time> <x> <y> <z> <yaw> <pitch>` with the angles in degrees
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut path = Self::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let values = line
+                .split_whitespace()
+                .map(str::parse::<f32>)
+                .collect::<std::result::Result<Vec<f32>, _>>()
+                .with_context(|| format!("line {}: expected numbers", number + 1))?;
+            let &[time, x, y, z, yaw, pitch] = values.as_slice() else {
+                bail!(
+                    "line {}: expected 6 values, got {}",
+                    number + 1,
+                    values.len()
+                );
+            };
+
+            path.add_keyframe(Keyframe {
+                time,
+                position: Point3::new(x, y, z),
+                yaw: Deg(yaw).into(),
+                pitch: clamp_pitch(Deg(pitch).into()),
+            });
+        }
+
+        Ok(path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("can't read camera path {}", path.display()))?;
+        Self::parse(&text)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.to_string())?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for CameraPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# time x y z yaw pitch, seconds and degrees")?;
+        for keyframe in &self.keyframes {
+            writeln!(
+                f,
+                "{} {} {} {} {} {}",
+                keyframe.time,
+                keyframe.position.x,
+                keyframe.position.y,
+                keyframe.position.z,
+                Deg::from(keyframe.yaw).0,
+                Deg::from(keyframe.pitch).0
+            )?;
+        }
+        std::result::Result::Ok(())
+    }
+}
+
+/// Plays a `CameraPath` back on a camera, `speed` times as fast as it was recorded
+pub struct CameraPathPlayer {
+    pub path: CameraPath,
+    pub speed: f32,
+    /// starts over after the last keyframe instead of stopping there
+    pub looping: bool,
+    time: f32,
+    playing: bool,
+}
+
+impl Default for CameraPathPlayer {
+    fn default() -> Self {
+        Self::new(CameraPath::new())
+    }
+}
+
+impl CameraPathPlayer {
+    pub fn new(path: CameraPath) -> Self {
+        Self {
+            path,
+            speed: 1.0,
+            looping: false,
+            time: 0.0,
+            playing: false,
+        }
+    }
+
+    /// plays from the start
+    pub fn play(&mut self) {
+        self.time = 0.0;
+        self.playing = !self.path.keyframes.is_empty();
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.clamp(0.0, self.path.duration());
+    }
+
+    /// Advances by `dt` and moves `camera` along the path.
+    ///
+    /// `false` when not playing, so the camera controller can have the camera instead. A path
+    /// that isn't looping stops on its last keyframe.
+    pub fn update(&mut self, camera: &mut Camera, dt: instant::Duration) -> bool {
+        if !self.playing {
+            return false;
+        }
+
+        let duration = self.path.duration();
+        self.time += dt.as_secs_f32() * self.speed;
+        if self.time >= duration {
+            if self.looping && duration > 0.0 {
+                self.time %= duration;
+            } else {
+                self.time = duration;
+                self.playing = false;
+            }
+        }
+
+        self.path.apply(camera, self.time)
+    }
+}
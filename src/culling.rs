@@ -0,0 +1,186 @@
+use cgmath::*;
+use std::cell::Cell;
+
+use crate::object::{Object, Vertex};
+
+/// Axis-aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Self {
+        Self { min, max }
+    }
+
+    /// box around `points`, `None` if there aren't any
+    pub fn from_points<I: IntoIterator<Item = Point3<f32>>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, point| aabb.grow(point)))
+    }
+
+    /// box around the vertex positions, an empty mesh gets an empty box at the origin
+    pub fn from_vertices(vertices: &[Vertex]) -> Self {
+        Self::from_points(vertices.iter().map(|vertex| Point3::from(vertex.position)))
+            .unwrap_or_else(|| Self::new(Point3::origin(), Point3::origin()))
+    }
+
+    pub fn grow(self, point: Point3<f32>) -> Self {
+        Self::new(
+            Point3::new(
+                self.min.x.min(point.x),
+                self.min.y.min(point.y),
+                self.min.z.min(point.z),
+            ),
+            Point3::new(
+                self.max.x.max(point.x),
+                self.max.y.max(point.y),
+                self.max.z.max(point.z),
+            ),
+        )
+    }
+
+    pub fn union(self, other: Aabb) -> Self {
+        self.grow(other.min).grow(other.max)
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    /// half the size along each axis
+    pub fn extents(&self) -> Vector3<f32> {
+        (self.max - self.min) * 0.5
+    }
+
+    /// box around this one after `matrix`, which can be looser than the transformed shape
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let center = matrix.transform_point(self.center());
+        let extents = self.extents();
+        // each axis of the new box gets the absolute contribution of every old axis
+        let linear = Matrix3::from_cols(
+            matrix.x.truncate(),
+            matrix.y.truncate(),
+            matrix.z.truncate(),
+        );
+        let extents = Vector3::new(
+            linear.row(0).map(f32::abs).dot(extents),
+            linear.row(1).map(f32::abs).dot(extents),
+            linear.row(2).map(f32::abs).dot(extents),
+        );
+        Self::new(center - extents, center + extents)
+    }
+}
+
+/// Plane where `normal.dot(point) + distance` is the signed distance of `point`, positive inside
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    fn from_row(row: Vector4<f32>) -> Self {
+        let length = row.truncate().magnitude();
+        Self {
+            normal: row.truncate() / length,
+            distance: row.w / length,
+        }
+    }
+
+    pub fn signed_distance(&self, point: Point3<f32>) -> f32 {
+        self.normal.dot(point.to_vec()) + self.distance
+    }
+}
+
+/// The volume a camera sees, as six planes facing inwards
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    /// left, right, bottom, top, near, far
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// planes of a view-projection matrix with wgpu's 0..1 clip depth, perspective or orthographic
+    pub fn from_matrix(view_proj: Matrix4<f32>) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| view_proj.row(i));
+        Self {
+            planes: [
+                Plane::from_row(w + x),
+                Plane::from_row(w - x),
+                Plane::from_row(w + y),
+                Plane::from_row(w - y),
+                Plane::from_row(z),
+                Plane::from_row(w - z),
+            ],
+        }
+    }
+
+    /// `false` only if the box is entirely outside one of the planes, so a few boxes near the
+    /// corners pass without being visible
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        let center = aabb.center();
+        let extents = aabb.extents();
+        self.planes.iter().all(|plane| {
+            let radius = plane.normal.map(f32::abs).dot(extents);
+            plane.signed_distance(center) >= -radius
+        })
+    }
+
+    pub fn contains_point(&self, point: Point3<f32>) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    /// Indices of the objects whose bounds are in view, counted in `stats`.
+    ///
+    /// Only for passes that draw what the camera sees, a shadow map still needs the casters
+    /// outside the view.
+    pub fn visible_objects(&self, objects: &[Object], stats: &CullStats) -> Vec<usize> {
+        let visible: Vec<usize> = objects
+            .iter()
+            .enumerate()
+            .filter(|(_, object)| self.intersects_aabb(&object.world_bounds()))
+            .map(|(i, _)| i)
+            .collect();
+        stats.count(visible.len() as u32, (objects.len() - visible.len()) as u32);
+        visible
+    }
+}
+
+/// Objects drawn and culled, summed over every pass of a frame.
+///
+/// Counting goes through a shared reference since rendering only gets `&Engine`. The engine
+/// calls `end_frame` after the render command, `visible()` and `culled()` report the last
+/// finished frame.
+#[derive(Debug, Default)]
+pub struct CullStats {
+    visible: Cell<u32>,
+    culled: Cell<u32>,
+    frame_visible: u32,
+    frame_culled: u32,
+}
+
+impl CullStats {
+    pub fn count(&self, visible: u32, culled: u32) {
+        self.visible.set(self.visible.get() + visible);
+        self.culled.set(self.culled.get() + culled);
+    }
+
+    pub fn visible(&self) -> u32 {
+        self.frame_visible
+    }
+
+    pub fn culled(&self) -> u32 {
+        self.frame_culled
+    }
+
+    pub fn end_frame(&mut self) {
+        self.frame_visible = self.visible.take();
+        self.frame_culled = self.culled.take();
+    }
+}
@@ -1,13 +1,15 @@
+use crate::antialias::AntiAliasing;
+use crate::input::Input;
+use crate::input_map::{self, InputMap};
 use crate::renderer::*;
 use crate::scene::*;
 use crate::texture;
 
 use std::sync::Arc;
 use winit::dpi::PhysicalSize;
-use winit::event::{DeviceEvent, ElementState, Event, KeyEvent, WindowEvent};
+use winit::event::{DeviceEvent, Event, WindowEvent};
 use winit::event_loop::EventLoop;
-use winit::keyboard::{KeyCode, PhysicalKey};
-use winit::window::{Window, WindowBuilder};
+use winit::window::{CursorGrabMode, Window, WindowBuilder};
 
 pub struct CommandBundle {
     pub input_command: Box<dyn Fn(&mut Engine, &WindowEvent) -> bool>,
@@ -80,6 +82,7 @@ pub struct Engine {
     // wgpu graphics components
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
+    adapter: wgpu::Adapter,
 
     // window, surface
     window: Arc<Window>,
@@ -90,7 +93,12 @@ pub struct Engine {
     // managing objects
     pub renderer: Renderer,
     pub scene: Scene,
+    /// keys and buttons held, pressed or released this frame, mouse motion and scroll
+    pub input: Input,
+    /// what keys, buttons and the wheel mean, for the engine's own controls and the camera controllers
+    pub input_map: InputMap,
     pub mouse_pressed: bool,
+    cursor_grabbed: bool,
     pub start_time: instant::Instant,
     pub last_render_time: instant::Instant,
 }
@@ -100,6 +108,30 @@ impl Engine {
         &self.window
     }
 
+    pub fn cursor_grabbed(&self) -> bool {
+        self.cursor_grabbed
+    }
+
+    /// Hides the cursor and locks it in place, or confines it to the window where locking isn't
+    /// supported. Mouse motion keeps coming as raw device deltas and goes to the camera
+    /// controller without a button held. Released again when the window loses focus
+    pub fn set_cursor_grab(&mut self, grab: bool) {
+        let window = &self.window;
+        if grab {
+            let result = window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined));
+            if let Err(e) = result {
+                eprintln!("can't grab the cursor: {}", e);
+                return;
+            }
+        } else if let Err(e) = window.set_cursor_grab(CursorGrabMode::None) {
+            eprintln!("can't release the cursor: {}", e);
+        }
+        window.set_cursor_visible(!grab);
+        self.cursor_grabbed = grab;
+    }
+
     fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
@@ -107,8 +139,157 @@ impl Engine {
             self.config.height = new_size.height;
             self.surface.configure(&self.device, &self.config);
 
-            let depth = texture::Texture::create_depth_texture(&self.device, &self.config, None);
-            self.renderer.depth_texture = Some(depth);
+            if let Some(hdr_pass) = self.renderer.hdr_pass.as_mut() {
+                hdr_pass.resize(&self.device, new_size.width, new_size.height);
+
+                if let Some(bloom_pass) = self.renderer.bloom_pass.as_mut() {
+                    bloom_pass.resize(&self.device, &hdr_pass.target);
+                }
+            }
+
+            self.renderer.render_target_manager.resize(
+                &self.device,
+                new_size.width,
+                new_size.height,
+            );
+
+            self.resize_cameras();
+            self.create_scene_targets();
+        }
+    }
+
+    /// fits the projections of the main camera and the scene's extra cameras to their viewports
+    fn resize_cameras(&mut self) {
+        let targets = &self.renderer.render_target_manager;
+        let main_view = &self.scene.main_view;
+        if let Some((width, height)) =
+            main_view.target_size(self.size.width, self.size.height, targets)
+        {
+            let [_, _, width, height] = main_view.viewport.pixels(width, height);
+            self.scene.projection.resize(width as u32, height as u32);
+        }
+
+        for view in &mut self.scene.cameras {
+            if let Some((width, height)) =
+                view.settings
+                    .target_size(self.size.width, self.size.height, targets)
+            {
+                view.resize(width, height);
+            }
+        }
+    }
+
+    /// what the adapter supports beyond WebGPU's baseline, e.g. for `ShadowPass::new`
+    pub fn downlevel_capabilities(&self) -> wgpu::DownlevelCapabilities {
+        self.adapter.get_downlevel_capabilities()
+    }
+
+    /// sample counts usable for both the scene color target and the depth target
+    pub fn supported_sample_counts(&self) -> Vec<u32> {
+        // without this feature only the counts WebGPU guarantees (1 and 4) are allowed
+        let format_flags = |format: wgpu::TextureFormat| {
+            if self
+                .device
+                .features()
+                .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+            {
+                self.adapter.get_texture_format_features(format).flags
+            } else {
+                format
+                    .guaranteed_format_features(self.device.features())
+                    .flags
+            }
+        };
+
+        let color = format_flags(self.renderer.scene_format(&self.config));
+        let depth = format_flags(texture::Texture::DEPTH_FORMAT);
+        color
+            .supported_sample_counts()
+            .into_iter()
+            .filter(|&count| depth.sample_count_supported(count))
+            .collect()
+    }
+
+    fn create_scene_targets(&mut self) {
+        let sample_count = self.renderer.sample_count;
+        let depth = texture::Texture::create_multisampled_depth_texture(
+            &self.device,
+            &self.config,
+            sample_count,
+            None,
+        );
+
+        if let (Some(antialias_pass), Some(hdr_pass)) = (
+            self.renderer.antialias_pass.as_mut(),
+            self.renderer.hdr_pass.as_ref(),
+        ) {
+            antialias_pass.resize(&self.device, &hdr_pass.target, &depth);
+        }
+        if let Some(gpu_culling_pass) = self.renderer.gpu_culling_pass.as_mut() {
+            gpu_culling_pass.resize(&self.device, &depth);
+        }
+        self.renderer.depth_texture = Some(depth);
+
+        self.renderer.msaa_texture = (sample_count > 1).then(|| {
+            texture::Texture::create_msaa_target(
+                &self.device,
+                self.config.width,
+                self.config.height,
+                self.renderer.scene_format(&self.config),
+                sample_count,
+                Some("MSAA Target"),
+            )
+        });
+    }
+
+    /// Applies `RenderState::sample_count` if it changed: falls back to the closest supported count below it,
+    /// recreates the color and depth targets and rebuilds the pipelines through the pipeline builder.
+    /// TAA needs a single sampled depth texture, MSAA is suspended while it's on
+    pub fn apply_sample_count(&mut self) {
+        let requested = match self.renderer.state.anti_aliasing {
+            AntiAliasing::Taa => 1,
+            _ => self.renderer.state.sample_count,
+        };
+        if requested == self.renderer.sample_count {
+            return;
+        }
+
+        let supported = self.supported_sample_counts();
+        let sample_count = supported
+            .iter()
+            .copied()
+            .filter(|&count| count <= requested)
+            .max()
+            .unwrap_or(1);
+        if sample_count != requested {
+            eprintln!(
+                "MSAA x{} isn't supported (supported: {:?}), using x{}",
+                requested, supported, sample_count
+            );
+            self.renderer.state.sample_count = sample_count;
+        }
+        if sample_count == self.renderer.sample_count {
+            return;
+        }
+
+        if !self.renderer.pipeline_manager.has_builder() {
+            eprintln!(
+                "pipelines were added without a builder and can't be rebuilt, keeping MSAA off"
+            );
+            self.renderer.state.sample_count = self.renderer.sample_count;
+            return;
+        }
+
+        self.renderer.sample_count = sample_count;
+        self.create_scene_targets();
+        self.renderer
+            .render_target_manager
+            .set_sample_count(&self.device, sample_count);
+        self.renderer
+            .pipeline_manager
+            .rebuild(&self.device, sample_count);
+        if let Some(skybox_pass) = self.renderer.skybox_pass.as_mut() {
+            skybox_pass.set_sample_count(&self.device, sample_count);
         }
     }
 
@@ -134,7 +315,19 @@ impl Engine {
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    required_features: wgpu::Features::POLYGON_MODE_LINE,
+                    // multiview is optional, used for single pass cube map rendering when available
+                    // adapter specific format features unlock more MSAA sample counts
+                    // and block compressed formats are used by KTX2 textures when the adapter supports them
+                    // multi draw indirect with a first instance lets GPU culling batch its draws
+                    required_features: wgpu::Features::POLYGON_MODE_LINE
+                        | (adapter.features()
+                            & (wgpu::Features::MULTIVIEW
+                                | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+                                | wgpu::Features::TEXTURE_COMPRESSION_BC
+                                | wgpu::Features::TEXTURE_COMPRESSION_ASTC
+                                | wgpu::Features::TEXTURE_COMPRESSION_ETC2
+                                | wgpu::Features::MULTI_DRAW_INDIRECT
+                                | wgpu::Features::INDIRECT_FIRST_INSTANCE)),
                     required_limits: wgpu::Limits::default(),
                 },
                 None,
@@ -166,13 +359,17 @@ impl Engine {
         Self {
             device,
             queue,
+            adapter,
             window,
             surface,
             config,
             size,
             renderer: Default::default(),
             scene: Default::default(),
+            input: Default::default(),
+            input_map: Default::default(),
             mouse_pressed: false,
+            cursor_grabbed: false,
             start_time: instant::Instant::now(),
             last_render_time: instant::Instant::now(),
         }
@@ -209,6 +406,13 @@ pub async fn run(scene: Option<Scene>, commands: CommandBundle) {
             game.engine.scene.camera_controller = value.camera_controller;
             game.engine.scene.camera_uniform = value.camera_uniform;
             game.engine.scene.projection = value.projection;
+            game.engine.scene.main_view = value.main_view;
+            game.engine.scene.cameras.extend(value.cameras);
+            game.engine.scene.camera_path = value.camera_path;
+            game.engine.scene.lights.extend(value.lights);
+            game.engine.resize_cameras();
+
+            game.engine.apply_sample_count();
         }
         None => {
             eprintln!("No Entry Scene provided");
@@ -216,65 +420,80 @@ pub async fn run(scene: Option<Scene>, commands: CommandBundle) {
         }
     };
 
-    let _ = event_loop.run(move |event, control_flow| match event {
-        Event::DeviceEvent {
-            event: DeviceEvent::MouseMotion { delta },
-            ..
-        } => {
-            if game.engine.mouse_pressed {
-                game.engine
-                    .scene
-                    .camera_controller
-                    .process_mouse(delta.0, delta.1);
+    let _ = event_loop.run(move |event, control_flow| {
+        // the input state sees every event, including the ones the game's input command takes
+        match &event {
+            Event::WindowEvent { window_id, event } if *window_id == game.engine.window().id() => {
+                game.engine.input.process_window_event(event);
+                if let WindowEvent::Focused(false) = event {
+                    game.engine.scene.camera_controller.release_all();
+                    game.engine.mouse_pressed = false;
+                    if game.engine.cursor_grabbed {
+                        game.engine.set_cursor_grab(false);
+                    }
+                }
             }
+            Event::DeviceEvent { event, .. } => game.engine.input.process_device_event(event),
+            _ => {}
         }
-        Event::WindowEvent {
-            window_id,
-            ref event,
-        } if window_id == game.engine.window().id() && !game.input(event) => match event {
-            WindowEvent::CloseRequested
-            | WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        physical_key: PhysicalKey::Code(KeyCode::Escape),
-                        state: ElementState::Pressed,
-                        ..
-                    },
-                ..
-            } => {
-                control_flow.exit();
-            }
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        physical_key: PhysicalKey::Code(KeyCode::Space),
-                        state: ElementState::Pressed,
-                        ..
-                    },
+
+        match event {
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
                 ..
             } => {
-                game.engine.renderer.state.polygon_fill = !game.engine.renderer.state.polygon_fill;
-            }
-            WindowEvent::Resized(new_size) => {
-                game.engine.resize(*new_size);
-                game.engine
-                    .scene
-                    .projection
-                    .resize(new_size.width, new_size.height);
+                if game.engine.mouse_pressed || game.engine.cursor_grabbed {
+                    game.engine
+                        .scene
+                        .camera_controller
+                        .process_mouse(delta.0, delta.1);
+                }
             }
-            WindowEvent::RedrawRequested => {
-                game.update();
-                match game.render() {
-                    Ok(_) => {}
-                    Err(wgpu::SurfaceError::Lost) => game.engine.resize(game.engine.size),
-                    Err(e) => eprintln!("{:?}", e),
+            Event::WindowEvent {
+                window_id,
+                ref event,
+            } if window_id == game.engine.window().id() && !game.input(event) => match event {
+                WindowEvent::CloseRequested => {
+                    control_flow.exit();
+                }
+                _ if game.engine.input_map.pressed(input_map::QUIT, event) => {
+                    control_flow.exit();
+                }
+                _ if game
+                    .engine
+                    .input_map
+                    .pressed(input_map::TOGGLE_WIREFRAME, event) =>
+                {
+                    game.engine.renderer.state.polygon_fill =
+                        !game.engine.renderer.state.polygon_fill;
+                }
+                _ if game
+                    .engine
+                    .input_map
+                    .pressed(input_map::TOGGLE_CURSOR_GRAB, event) =>
+                {
+                    game.engine.set_cursor_grab(!game.engine.cursor_grabbed);
                 }
+                WindowEvent::Resized(new_size) => {
+                    game.engine.resize(*new_size);
+                }
+                WindowEvent::RedrawRequested => {
+                    game.engine.apply_sample_count();
+                    game.update();
+                    match game.render() {
+                        Ok(_) => {}
+                        Err(wgpu::SurfaceError::Lost) => game.engine.resize(game.engine.size),
+                        Err(e) => eprintln!("{:?}", e),
+                    }
+                    game.engine.input.end_frame();
+                    game.engine.renderer.cull_stats.end_frame();
+                }
+                _ => {}
+            },
+            Event::AboutToWait => {
+                game.engine.window().request_redraw();
             }
-            _ => {}
-        },
-        Event::AboutToWait => {
-            game.engine.window().request_redraw();
+            _ => (),
         }
-        _ => (),
     });
 }
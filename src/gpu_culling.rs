@@ -0,0 +1,738 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::*;
+use std::cell::Cell;
+use wgpu::util::DeviceExt;
+
+use crate::camera::CameraUniform;
+use crate::culling::Aabb;
+use crate::object::Mesh;
+use crate::texture::Texture;
+
+const CULL_WORKGROUP_SIZE: u32 = 64;
+const HI_Z_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Float;
+// size of a CullDraw uniform binding, the struct itself only has the one u32
+const DRAW_BINDING_SIZE: u64 = 16;
+
+/// One object for `GpuCullingPass`, in the instance buffer the vertex shader reads
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct CullInstance {
+    pub model: [[f32; 4]; 4],
+    pub bounds_min: [f32; 3],
+    /// index of the `CullBatch` the instance is drawn with
+    pub batch: u32,
+    pub bounds_max: [f32; 3],
+    _padding: u32,
+}
+
+impl CullInstance {
+    /// `bounds` in model space, usually the mesh's
+    pub fn new(model: Matrix4<f32>, bounds: &Aabb, batch: u32) -> Self {
+        Self {
+            model: model.into(),
+            bounds_min: bounds.min.into(),
+            batch,
+            bounds_max: bounds.max.into(),
+            _padding: 0,
+        }
+    }
+}
+
+/// Indices drawn once per visible instance of the batch, with one indirect draw per batch
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CullBatch {
+    /// index into the meshes passed to `GpuCullingPass::draw`
+    pub mesh: usize,
+    pub first_index: u32,
+    pub index_count: u32,
+    pub base_vertex: i32,
+}
+
+impl CullBatch {
+    pub fn whole_mesh(index: usize, mesh: &Mesh) -> Self {
+        Self {
+            mesh: index,
+            first_index: 0,
+            index_count: mesh.indices.len() as u32,
+            base_vertex: 0,
+        }
+    }
+}
+
+// wgpu's indexed indirect draw arguments
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct CullParams {
+    view_proj: [[f32; 4]; 4],
+    planes: [[f32; 4]; 6],
+    instance_count: u32,
+    hi_z: u32,
+    hi_z_mips: u32,
+    _padding: u32,
+    hi_z_size: [f32; 2],
+    _padding2: [f32; 2],
+}
+
+// buffers the cull shader binds
+struct CullBuffers {
+    instances: wgpu::Buffer,
+    batch_starts: wgpu::Buffer,
+    draws: wgpu::Buffer,
+    visible: wgpu::Buffer,
+    params: wgpu::Buffer,
+}
+
+impl CullBuffers {
+    fn bind_group(
+        &self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        hi_z: &wgpu::TextureView,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("GPU Culling Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.instances.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.batch_starts.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.draws.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.visible.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(hi_z),
+                },
+            ],
+        })
+    }
+}
+
+// farthest depth pyramid of a depth texture, rebuilt every frame by `build_hi_z`
+struct HiZPyramid {
+    view: wgpu::TextureView,
+    width: u32,
+    height: u32,
+    mips: u32,
+    mip_views: Vec<wgpu::TextureView>,
+    copy_pipeline: wgpu::RenderPipeline,
+    copy_bind_group: wgpu::BindGroup,
+    // downsample_bind_groups[i] samples mip i to render mip i + 1
+    downsample_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+/// Frustum (and optionally occlusion) culling on the GPU for scenes with too many objects to
+/// cull and draw one by one.
+///
+/// `cull` runs a compute pass that tests every instance's bounds, compacts the visible ones into
+/// a list per batch and writes each batch's instance count into an indirect draw. `draw` then
+/// issues those draws, with `multi_draw_indexed_indirect` for batches sharing a mesh when the
+/// device has `MULTI_DRAW_INDIRECT` and `INDIRECT_FIRST_INSTANCE`. Vertex shaders get the drawn
+/// instance from `culled_instance(instance_index)`, see `shader_snippet`.
+///
+/// With `hi_z` on, instances hidden behind the previous frame's depth are culled too.
+/// `build_hi_z` turns the depth texture into a pyramid after the main pass, so the test lags a
+/// frame behind and something can pop in for a frame when the camera moves fast.
+pub struct GpuCullingPass {
+    pub hi_z: bool,
+    pub instance_bind_group_layout: wgpu::BindGroupLayout,
+    pub instance_bind_group: wgpu::BindGroup,
+    batches: Vec<CullBatch>,
+    instance_count: u32,
+    buffers: CullBuffers,
+    // indirect draws with no instances, copied over `buffers.draws` before every cull
+    indirect_reset_buffer: wgpu::Buffer,
+    draw_alignment: u32,
+    cull_pipeline: wgpu::ComputePipeline,
+    cull_bind_group_layout: wgpu::BindGroupLayout,
+    cull_bind_group: wgpu::BindGroup,
+    downsample_pipeline: wgpu::RenderPipeline,
+    downsample_bind_group_layout: wgpu::BindGroupLayout,
+    pyramid: Option<HiZPyramid>,
+    // whether the pyramid holds a frame's depth yet
+    pyramid_ready: Cell<bool>,
+    multi_draw: bool,
+}
+
+impl GpuCullingPass {
+    /// Every instance's `batch` has to index into `batches` and stay in the same batch, since
+    /// each batch's share of the visible list is sized by how many instances it starts out with.
+    /// Call `resize` with the depth texture before using `hi_z`
+    pub fn new(device: &wgpu::Device, instances: &[CullInstance], batches: &[CullBatch]) -> Self {
+        let multi_draw = device.features().contains(
+            wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::INDIRECT_FIRST_INSTANCE,
+        );
+
+        // each batch gets a range of the visible list as long as its instance count
+        let mut batch_starts = vec![0u32; batches.len() + 1];
+        for instance in instances {
+            batch_starts[instance.batch as usize + 1] += 1;
+        }
+        for i in 1..batch_starts.len() {
+            batch_starts[i] += batch_starts[i - 1];
+        }
+
+        let draws: Vec<DrawArgs> = batches
+            .iter()
+            .zip(&batch_starts)
+            .map(|(batch, &start)| DrawArgs {
+                index_count: batch.index_count,
+                instance_count: 0,
+                first_index: batch.first_index,
+                base_vertex: batch.base_vertex,
+                first_instance: if multi_draw { start } else { 0 },
+            })
+            .collect();
+
+        // one CullDraw per batch with its start for draws without a first instance, and a last
+        // one that's 0 for multi draws
+        let draw_alignment = device.limits().min_uniform_buffer_offset_alignment;
+        let mut draw_data = vec![0u8; draw_alignment as usize * (batches.len() + 1)];
+        if !multi_draw {
+            for (i, start) in batch_starts[..batches.len()].iter().enumerate() {
+                let offset = i * draw_alignment as usize;
+                draw_data[offset..offset + 4].copy_from_slice(&start.to_le_bytes());
+            }
+        }
+
+        let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cull Instances"),
+            contents: bytemuck::cast_slice(instances),
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+        });
+        let batch_start_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cull Batch Starts"),
+            contents: bytemuck::cast_slice(&batch_starts),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let visible_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cull Visible Instances"),
+            size: (instances.len().max(1) * std::mem::size_of::<u32>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let indirect_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cull Indirect Draws"),
+            size: (draws.len().max(1) * std::mem::size_of::<DrawArgs>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let indirect_reset_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cull Indirect Reset"),
+            contents: bytemuck::cast_slice(&draws),
+            usage: wgpu::BufferUsages::COPY_SRC,
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cull Params"),
+            size: std::mem::size_of::<CullParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let draw_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Cull Draws"),
+            contents: &draw_data,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let storage_entry = |binding, visibility, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let pyramid_entry = |binding, visibility| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let compute = wgpu::ShaderStages::COMPUTE;
+        let cull_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("GPU Culling Layout"),
+                entries: &[
+                    storage_entry(0, compute, true),
+                    storage_entry(1, compute, true),
+                    storage_entry(2, compute, false),
+                    storage_entry(3, compute, false),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: compute,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    pyramid_entry(5, compute),
+                ],
+            });
+        let instance_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Cull Instance Layout"),
+                entries: &[
+                    storage_entry(0, wgpu::ShaderStages::VERTEX, true),
+                    storage_entry(1, wgpu::ShaderStages::VERTEX, true),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: wgpu::BufferSize::new(DRAW_BINDING_SIZE),
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let downsample_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Hi-Z Downsample Layout"),
+                entries: &[pyramid_entry(0, wgpu::ShaderStages::FRAGMENT)],
+            });
+
+        let cull_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("GPU Culling Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/gpu_cull.wgsl").into()),
+        });
+
+        let cull_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("GPU Culling Pipeline Layout"),
+            bind_group_layouts: &[&cull_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let cull_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("GPU Culling Pipeline"),
+            layout: Some(&cull_pipeline_layout),
+            module: &cull_shader,
+            entry_point: "cull",
+        });
+        let downsample_pipeline = Self::create_hi_z_pipeline(
+            device,
+            "Hi-Z Downsample",
+            &downsample_bind_group_layout,
+            include_str!("shaders/hi_z_downsample.wgsl"),
+            "fs_downsample",
+        );
+
+        // bound in place of the pyramid until there is one
+        let empty_hi_z = Self::create_pyramid_texture(device, 1, 1, 1)
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let instance_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Cull Instance Bind Group"),
+            layout: &instance_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: instance_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: visible_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer: &draw_buffer,
+                        offset: 0,
+                        size: wgpu::BufferSize::new(DRAW_BINDING_SIZE),
+                    }),
+                },
+            ],
+        });
+
+        let buffers = CullBuffers {
+            instances: instance_buffer,
+            batch_starts: batch_start_buffer,
+            draws: indirect_buffer,
+            visible: visible_buffer,
+            params: params_buffer,
+        };
+        let cull_bind_group = buffers.bind_group(device, &cull_bind_group_layout, &empty_hi_z);
+
+        Self {
+            hi_z: false,
+            instance_bind_group_layout,
+            instance_bind_group,
+            batches: batches.to_vec(),
+            instance_count: instances.len() as u32,
+            buffers,
+            indirect_reset_buffer,
+            draw_alignment,
+            cull_pipeline,
+            cull_bind_group_layout,
+            cull_bind_group,
+            downsample_pipeline,
+            downsample_bind_group_layout,
+            pyramid: None,
+            pyramid_ready: Cell::new(false),
+            multi_draw,
+        }
+    }
+
+    fn create_pyramid_texture(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        mips: u32,
+    ) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Hi-Z Pyramid"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: mips,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HI_Z_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+    }
+
+    // full screen pass rendering one pyramid mip, `source` is appended to fullscreen.wgsl
+    fn create_hi_z_pipeline(
+        device: &wgpu::Device,
+        label: &str,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        source: &str,
+        entry_point: &str,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&format!("{} Shader", label)),
+            source: wgpu::ShaderSource::Wgsl(
+                (include_str!("shaders/fullscreen.wgsl").to_string() + source).into(),
+            ),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&format!("{} Pipeline Layout", label)),
+            bind_group_layouts: &[bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&format!("{} Pipeline", label)),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: HI_Z_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    }
+
+    fn create_pyramid(&self, device: &wgpu::Device, depth: &Texture) -> HiZPyramid {
+        // power of two sizes halve exactly down the chain, so the same uv finds the texels covering
+        // a box in every mip
+        let width = depth.texture.width().next_power_of_two();
+        let height = depth.texture.height().next_power_of_two();
+        let mips = 32 - width.max(height).leading_zeros();
+        let texture = Self::create_pyramid_texture(device, width, height, mips);
+        let mip_views: Vec<wgpu::TextureView> = (0..mips)
+            .map(|mip| {
+                texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("Hi-Z Mip"),
+                    base_mip_level: mip,
+                    mip_level_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        let sample_count = depth.texture.sample_count();
+        let multisampled = sample_count > 1;
+        let copy_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Hi-Z Copy Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled,
+                    },
+                    count: None,
+                }],
+            });
+
+        let mut source = include_str!("shaders/hi_z_copy.wgsl").to_string();
+        if multisampled {
+            source = source
+                .replace("texture_2d<f32>", "texture_multisampled_2d<f32>")
+                .replace(
+                    "const SAMPLES: i32 = 1;",
+                    &format!("const SAMPLES: i32 = {};", sample_count),
+                );
+        }
+        let copy_pipeline = Self::create_hi_z_pipeline(
+            device,
+            "Hi-Z Copy",
+            &copy_bind_group_layout,
+            &source,
+            "fs_copy_depth",
+        );
+
+        let copy_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Hi-Z Copy Bind Group"),
+            layout: &copy_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&depth.view),
+            }],
+        });
+        let downsample_bind_groups = mip_views[..mip_views.len() - 1]
+            .iter()
+            .map(|source| {
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Hi-Z Downsample Bind Group"),
+                    layout: &self.downsample_bind_group_layout,
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(source),
+                    }],
+                })
+            })
+            .collect();
+
+        HiZPyramid {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            width,
+            height,
+            mips,
+            mip_views,
+            copy_pipeline,
+            copy_bind_group,
+            downsample_bind_groups,
+        }
+    }
+
+    /// rebuilds the Hi-Z pyramid for a new depth texture, called from the engine
+    pub fn resize(&mut self, device: &wgpu::Device, depth: &Texture) {
+        let pyramid = self.create_pyramid(device, depth);
+        self.cull_bind_group =
+            self.buffers
+                .bind_group(device, &self.cull_bind_group_layout, &pyramid.view);
+        self.pyramid = Some(pyramid);
+        self.pyramid_ready.set(false);
+    }
+
+    pub fn instance_count(&self) -> u32 {
+        self.instance_count
+    }
+
+    /// overwrites instances from `first` on, e.g. for objects that moved, they can't change batch
+    pub fn write_instances(&self, queue: &wgpu::Queue, first: u32, instances: &[CullInstance]) {
+        assert!(
+            first as usize + instances.len() <= self.instance_count as usize,
+            "writing past the instances the pass was created with"
+        );
+        queue.write_buffer(
+            &self.buffers.instances,
+            (first as usize * std::mem::size_of::<CullInstance>()) as wgpu::BufferAddress,
+            bytemuck::cast_slice(instances),
+        );
+    }
+
+    /// Culls the instances against what `camera` sees and fills the indirect draws.
+    /// Record it before the pass that calls `draw`
+    pub fn cull(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        queue: &wgpu::Queue,
+        camera: &CameraUniform,
+    ) {
+        let pyramid = self
+            .pyramid
+            .as_ref()
+            .filter(|_| self.hi_z && self.pyramid_ready.get());
+        let frustum = camera.frustum();
+        let params = CullParams {
+            view_proj: camera.view_proj().into(),
+            planes: frustum
+                .planes
+                .map(|plane| plane.normal.extend(plane.distance).into()),
+            instance_count: self.instance_count,
+            hi_z: pyramid.is_some() as u32,
+            hi_z_mips: pyramid.map_or(1, |pyramid| pyramid.mips),
+            _padding: 0,
+            hi_z_size: pyramid.map_or([1.0, 1.0], |pyramid| {
+                [pyramid.width as f32, pyramid.height as f32]
+            }),
+            _padding2: [0.0; 2],
+        };
+        queue.write_buffer(&self.buffers.params, 0, bytemuck::cast_slice(&[params]));
+
+        encoder.copy_buffer_to_buffer(
+            &self.indirect_reset_buffer,
+            0,
+            &self.buffers.draws,
+            0,
+            self.indirect_reset_buffer.size(),
+        );
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("GPU Culling Pass"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.cull_pipeline);
+        compute_pass.set_bind_group(0, &self.cull_bind_group, &[]);
+        compute_pass.dispatch_workgroups(self.instance_count.div_ceil(CULL_WORKGROUP_SIZE), 1, 1);
+    }
+
+    /// Turns the depth texture into the pyramid the next frame's `cull` tests against.
+    /// Record it after the pass that wrote the depth, does nothing while `hi_z` is off
+    pub fn build_hi_z(&self, encoder: &mut wgpu::CommandEncoder) {
+        let Some(pyramid) = self.pyramid.as_ref().filter(|_| self.hi_z) else {
+            self.pyramid_ready.set(false);
+            return;
+        };
+
+        let mut draw = |label, pipeline, bind_group, output| {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: output,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        };
+
+        draw(
+            "Hi-Z Copy",
+            &pyramid.copy_pipeline,
+            &pyramid.copy_bind_group,
+            &pyramid.mip_views[0],
+        );
+        for (bind_group, output) in pyramid
+            .downsample_bind_groups
+            .iter()
+            .zip(&pyramid.mip_views[1..])
+        {
+            draw(
+                "Hi-Z Downsample",
+                &self.downsample_pipeline,
+                bind_group,
+                output,
+            );
+        }
+
+        self.pyramid_ready.set(true);
+    }
+
+    /// Draws the visible instances, `group` is where the pipeline has `instance_bind_group_layout`
+    /// and `meshes` are what the batches' `mesh` index into
+    pub fn draw<'a>(
+        &'a self,
+        render_pass: &mut wgpu::RenderPass<'a>,
+        group: u32,
+        meshes: &[&'a Mesh],
+    ) {
+        let args_size = std::mem::size_of::<DrawArgs>() as wgpu::BufferAddress;
+        let set_mesh = |render_pass: &mut wgpu::RenderPass<'a>, mesh: &'a Mesh| {
+            render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        };
+
+        if self.multi_draw {
+            // the first instance in the draws already points at each batch's range
+            let offset = self.draw_alignment * self.batches.len() as u32;
+            render_pass.set_bind_group(group, &self.instance_bind_group, &[offset]);
+
+            // one call for each run of batches with the same mesh
+            let mut start = 0;
+            while start < self.batches.len() {
+                let mesh = self.batches[start].mesh;
+                let count = self.batches[start..]
+                    .iter()
+                    .take_while(|batch| batch.mesh == mesh)
+                    .count();
+                set_mesh(render_pass, meshes[mesh]);
+                render_pass.multi_draw_indexed_indirect(
+                    &self.buffers.draws,
+                    start as wgpu::BufferAddress * args_size,
+                    count as u32,
+                );
+                start += count;
+            }
+        } else {
+            for (i, batch) in self.batches.iter().enumerate() {
+                let offset = self.draw_alignment * i as u32;
+                render_pass.set_bind_group(group, &self.instance_bind_group, &[offset]);
+                set_mesh(render_pass, meshes[batch.mesh]);
+                render_pass.draw_indexed_indirect(
+                    &self.buffers.draws,
+                    i as wgpu::BufferAddress * args_size,
+                );
+            }
+        }
+    }
+}
+
+/// WGSL declarations for the instance bind group placed at bind group `group`.
+/// Prepend it to a shader source to get `culled_instance`.
+pub fn shader_snippet(group: u32) -> String {
+    include_str!("shaders/gpu_cull_instance.wgsl")
+        .replace("@group(0)", &format!("@group({})", group))
+}
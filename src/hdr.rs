@@ -0,0 +1,487 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+use crate::renderer::RenderState;
+use crate::texture;
+
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+const HISTOGRAM_BINS: u64 = 256;
+const HISTOGRAM_WORKGROUP_SIZE: u32 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tonemapper {
+    /// clamp only
+    None,
+    Reinhard,
+    Aces,
+    AgX,
+}
+
+impl Tonemapper {
+    /// next operator, for cycling through them at runtime
+    pub fn next(self) -> Self {
+        match self {
+            Tonemapper::None => Tonemapper::Reinhard,
+            Tonemapper::Reinhard => Tonemapper::Aces,
+            Tonemapper::Aces => Tonemapper::AgX,
+            Tonemapper::AgX => Tonemapper::None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exposure {
+    /// scene color is multiplied by 2^ev
+    Manual { ev: f32 },
+    /// average scene luminance, measured with a GPU histogram over `min_ev..max_ev` (log2 luminance),
+    /// is mapped to middle grey. `speed` controls how fast the eye adapts, `compensation` is added in EV
+    Auto {
+        min_ev: f32,
+        max_ev: f32,
+        speed: f32,
+        compensation: f32,
+    },
+}
+
+impl Exposure {
+    pub fn auto() -> Self {
+        Exposure::Auto {
+            min_ev: -8.0,
+            max_ev: 4.0,
+            speed: 1.5,
+            compensation: 0.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct TonemapParams {
+    tonemapper: u32,
+    auto_exposure: u32,
+    encode_srgb: u32,
+    exposure: f32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct HistogramParams {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    adaptation: f32,
+    pixel_count: u32,
+}
+
+/// Floating point scene target plus the pass that brings it down to the swapchain.
+///
+/// Scene pipelines render into `target` (`HDR_FORMAT`), then `render` measures the scene
+/// luminance when auto exposure is on and tonemaps `target` into the output view.
+/// Tonemapper and exposure are read from `RenderState`.
+pub struct HdrPass {
+    pub target: texture::Texture,
+    output_format: wgpu::TextureFormat,
+    tonemap_pipeline: wgpu::RenderPipeline,
+    tonemap_bind_group_layout: wgpu::BindGroupLayout,
+    tonemap_bind_group: wgpu::BindGroup,
+    tonemap_buffer: wgpu::Buffer,
+    histogram_pipeline: wgpu::ComputePipeline,
+    average_pipeline: wgpu::ComputePipeline,
+    histogram_bind_group_layout: wgpu::BindGroupLayout,
+    histogram_bind_group: wgpu::BindGroup,
+    histogram_buffer: wgpu::Buffer,
+    histogram_params_buffer: wgpu::Buffer,
+    exposure_buffer: wgpu::Buffer,
+    auto_exposure: bool,
+}
+
+impl HdrPass {
+    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Self {
+        let target = Self::create_target(device, config.width, config.height);
+
+        let tonemap_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Tonemap Params"),
+            size: std::mem::size_of::<TonemapParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let histogram_params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Histogram Params"),
+            size: std::mem::size_of::<HistogramParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let histogram_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Luminance Histogram"),
+            size: HISTOGRAM_BINS * std::mem::size_of::<u32>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        // starts at middle grey so the first frames aren't blown out
+        let exposure_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Exposure State"),
+            contents: bytemuck::cast_slice(&[0.18f32]),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let tonemap_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Tonemap Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let histogram_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Histogram Layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: false },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let tonemap_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Tonemap Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("shaders/fullscreen.wgsl"),
+                    include_str!("shaders/tonemap.wgsl")
+                )
+                .into(),
+            ),
+        });
+
+        let tonemap_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Tonemap Pipeline Layout"),
+                bind_group_layouts: &[&tonemap_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let tonemap_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Tonemap Pipeline"),
+            layout: Some(&tonemap_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &tonemap_shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &tonemap_shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let histogram_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Luminance Histogram Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                include_str!("shaders/luminance_histogram.wgsl").into(),
+            ),
+        });
+
+        let histogram_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Histogram Pipeline Layout"),
+                bind_group_layouts: &[&histogram_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let histogram_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Build Histogram Pipeline"),
+            layout: Some(&histogram_pipeline_layout),
+            module: &histogram_shader,
+            entry_point: "build_histogram",
+        });
+        let average_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Average Histogram Pipeline"),
+            layout: Some(&histogram_pipeline_layout),
+            module: &histogram_shader,
+            entry_point: "average_histogram",
+        });
+
+        let tonemap_bind_group = Self::create_tonemap_bind_group(
+            device,
+            &tonemap_bind_group_layout,
+            &target,
+            &tonemap_buffer,
+            &exposure_buffer,
+        );
+        let histogram_bind_group = Self::create_histogram_bind_group(
+            device,
+            &histogram_bind_group_layout,
+            &target,
+            &histogram_buffer,
+            &histogram_params_buffer,
+            &exposure_buffer,
+        );
+
+        Self {
+            target,
+            output_format: config.format,
+            tonemap_pipeline,
+            tonemap_bind_group_layout,
+            tonemap_bind_group,
+            tonemap_buffer,
+            histogram_pipeline,
+            average_pipeline,
+            histogram_bind_group_layout,
+            histogram_bind_group,
+            histogram_buffer,
+            histogram_params_buffer,
+            exposure_buffer,
+            auto_exposure: false,
+        }
+    }
+
+    fn create_target(device: &wgpu::Device, width: u32, height: u32) -> texture::Texture {
+        texture::Texture::create_color_target(device, width, height, HDR_FORMAT, Some("HDR Target"))
+    }
+
+    fn create_tonemap_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        target: &texture::Texture,
+        tonemap_buffer: &wgpu::Buffer,
+        exposure_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Tonemap Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&target.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&target.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: tonemap_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: exposure_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    fn create_histogram_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        target: &texture::Texture,
+        histogram_buffer: &wgpu::Buffer,
+        params_buffer: &wgpu::Buffer,
+        exposure_buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Histogram Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&target.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: histogram_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: exposure_buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// recreates the target to match the window, called from `Engine::resize`
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.target = Self::create_target(device, width, height);
+        self.tonemap_bind_group = Self::create_tonemap_bind_group(
+            device,
+            &self.tonemap_bind_group_layout,
+            &self.target,
+            &self.tonemap_buffer,
+            &self.exposure_buffer,
+        );
+        self.histogram_bind_group = Self::create_histogram_bind_group(
+            device,
+            &self.histogram_bind_group_layout,
+            &self.target,
+            &self.histogram_buffer,
+            &self.histogram_params_buffer,
+            &self.exposure_buffer,
+        );
+    }
+
+    /// uploads tonemapper and exposure settings, `dt` drives the eye adaptation
+    pub fn update(&mut self, queue: &wgpu::Queue, state: &RenderState, dt: instant::Duration) {
+        let (auto_exposure, exposure) = match state.exposure {
+            Exposure::Manual { ev } => (false, ev.exp2()),
+            Exposure::Auto {
+                min_ev,
+                max_ev,
+                speed,
+                compensation,
+            } => {
+                let size = self.target.texture.size();
+                queue.write_buffer(
+                    &self.histogram_params_buffer,
+                    0,
+                    bytemuck::cast_slice(&[HistogramParams {
+                        min_log_luminance: min_ev,
+                        log_luminance_range: (max_ev - min_ev).max(0.001),
+                        adaptation: 1.0 - (-dt.as_secs_f32() * speed).exp(),
+                        pixel_count: size.width * size.height,
+                    }]),
+                );
+                (true, compensation.exp2())
+            }
+        };
+        self.auto_exposure = auto_exposure;
+
+        queue.write_buffer(
+            &self.tonemap_buffer,
+            0,
+            bytemuck::cast_slice(&[TonemapParams {
+                tonemapper: match state.tonemapper {
+                    Tonemapper::None => 0,
+                    Tonemapper::Reinhard => 1,
+                    Tonemapper::Aces => 2,
+                    Tonemapper::AgX => 3,
+                },
+                auto_exposure: auto_exposure as u32,
+                encode_srgb: !self.output_format.is_srgb() as u32,
+                exposure,
+            }]),
+        );
+    }
+
+    /// Measures scene luminance (auto exposure only) and tonemaps `target` into `output`.
+    /// Call after the scene has been rendered into `target`.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        if self.auto_exposure {
+            let size = self.target.texture.size();
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Luminance Histogram Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_bind_group(0, &self.histogram_bind_group, &[]);
+            compute_pass.set_pipeline(&self.histogram_pipeline);
+            compute_pass.dispatch_workgroups(
+                size.width.div_ceil(HISTOGRAM_WORKGROUP_SIZE),
+                size.height.div_ceil(HISTOGRAM_WORKGROUP_SIZE),
+                1,
+            );
+            compute_pass.set_pipeline(&self.average_pipeline);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Tonemap Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.tonemap_pipeline);
+        render_pass.set_bind_group(0, &self.tonemap_bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
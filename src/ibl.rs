@@ -0,0 +1,655 @@
+use anyhow::*;
+use bytemuck::{Pod, Zeroable};
+use std::path::Path;
+use std::sync::Arc;
+use wgpu::util::DeviceExt;
+
+use crate::texture::Texture;
+
+pub const IRRADIANCE_SIZE: u32 = 32;
+pub const PREFILTERED_SIZE: u32 = 128;
+/// roughness 0 to 1 spread over the mips
+pub const PREFILTERED_MIPS: u32 = 5;
+pub const BRDF_LUT_SIZE: u32 = 256;
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+const BYTES_PER_PIXEL: u32 = 8;
+const WORKGROUP_SIZE: u32 = 8;
+const CACHE_MAGIC: &[u8; 4] = b"IBL2";
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct PrefilterParams {
+    roughness: f32,
+    environment_size: f32,
+    _padding: [f32; 2],
+}
+
+/// Precomputed image based lighting for one environment cube map.
+///
+/// `irradiance` is the cosine convolved environment for diffuse light, `prefiltered` holds the
+/// environment convolved with GGX lobes of increasing roughness in its mips, and `brdf_lut` the
+/// scale and bias applied to F0 by the split sum approximation. Generating takes a moment, so the
+/// result can be written to disk and loaded instead.
+pub struct EnvironmentMaps {
+    pub irradiance: Texture,
+    pub prefiltered: Texture,
+    pub brdf_lut: Texture,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl EnvironmentMaps {
+    fn create_textures(device: &wgpu::Device) -> (Texture, Texture, Texture) {
+        let usage = wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::STORAGE_BINDING
+            | wgpu::TextureUsages::COPY_SRC
+            | wgpu::TextureUsages::COPY_DST;
+
+        let irradiance = Texture::create_cube(
+            device,
+            IRRADIANCE_SIZE,
+            1,
+            FORMAT,
+            usage,
+            Some("Irradiance Map"),
+        );
+        let prefiltered = Texture::create_cube(
+            device,
+            PREFILTERED_SIZE,
+            PREFILTERED_MIPS,
+            FORMAT,
+            usage,
+            Some("Prefiltered Environment Map"),
+        );
+
+        let brdf_lut_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("BRDF LUT"),
+            size: wgpu::Extent3d {
+                width: BRDF_LUT_SIZE,
+                height: BRDF_LUT_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: FORMAT,
+            usage,
+            view_formats: &[],
+        });
+        let brdf_lut = Texture {
+            view: brdf_lut_texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            sampler: Arc::new(device.create_sampler(&wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            })),
+            texture: brdf_lut_texture,
+        };
+
+        (irradiance, prefiltered, brdf_lut)
+    }
+
+    fn from_textures(
+        device: &wgpu::Device,
+        irradiance: Texture,
+        prefiltered: Texture,
+        brdf_lut: Texture,
+    ) -> Self {
+        let cube_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::Cube,
+                multisampled: false,
+            },
+            count: None,
+        };
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("IBL Layout"),
+            entries: &[
+                cube_entry(0),
+                cube_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("IBL Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&irradiance.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&prefiltered.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&brdf_lut.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    // trilinear, the prefiltered map is sampled between mips
+                    resource: wgpu::BindingResource::Sampler(&prefiltered.sampler),
+                },
+            ],
+        });
+
+        Self {
+            irradiance,
+            prefiltered,
+            brdf_lut,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    /// runs the compute passes on `environment`, a cube map such as `Texture::cube_from_equirectangular` creates
+    pub fn generate(device: &wgpu::Device, queue: &wgpu::Queue, environment: &Texture) -> Self {
+        let (irradiance, prefiltered, brdf_lut) = Self::create_textures(device);
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("IBL Prefilter Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/ibl_prefilter.wgsl").into()),
+        });
+
+        let environment_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::Cube,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let sampler_entry = wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        let storage_entry = |binding, view_dimension| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: FORMAT,
+                view_dimension,
+            },
+            count: None,
+        };
+
+        let irradiance_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Irradiance Layout"),
+            entries: &[
+                environment_entry,
+                sampler_entry,
+                storage_entry(2, wgpu::TextureViewDimension::D2Array),
+            ],
+        });
+        let prefilter_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Prefilter Layout"),
+            entries: &[
+                environment_entry,
+                sampler_entry,
+                storage_entry(3, wgpu::TextureViewDimension::D2Array),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let brdf_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("BRDF LUT Layout"),
+            entries: &[storage_entry(5, wgpu::TextureViewDimension::D2)],
+        });
+
+        let create_pipeline = |layout: &wgpu::BindGroupLayout, entry_point: &str| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(entry_point),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point,
+            })
+        };
+        let irradiance_pipeline = create_pipeline(&irradiance_layout, "irradiance");
+        let prefilter_pipeline = create_pipeline(&prefilter_layout, "prefilter");
+        let brdf_pipeline = create_pipeline(&brdf_layout, "brdf_lut");
+
+        let irradiance_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Irradiance Bind Group"),
+            layout: &irradiance_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&environment.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&environment.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(
+                        &irradiance.create_layer_range_view(0, 6),
+                    ),
+                },
+            ],
+        });
+
+        let environment_size = environment.texture.width() as f32;
+        let prefilter_bind_groups: Vec<_> = (0..PREFILTERED_MIPS)
+            .map(|mip| {
+                let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Prefilter Params"),
+                    contents: bytemuck::cast_slice(&[PrefilterParams {
+                        roughness: mip as f32 / (PREFILTERED_MIPS - 1) as f32,
+                        environment_size,
+                        _padding: [0.0; 2],
+                    }]),
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+                let view = prefiltered
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor {
+                        dimension: Some(wgpu::TextureViewDimension::D2Array),
+                        base_mip_level: mip,
+                        mip_level_count: Some(1),
+                        ..Default::default()
+                    });
+
+                device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Prefilter Bind Group"),
+                    layout: &prefilter_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&environment.view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&environment.sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 3,
+                            resource: wgpu::BindingResource::TextureView(&view),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 4,
+                            resource: params.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+
+        let brdf_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("BRDF LUT Bind Group"),
+            layout: &brdf_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 5,
+                resource: wgpu::BindingResource::TextureView(&brdf_lut.view),
+            }],
+        });
+
+        let workgroups = |size: u32| size.div_ceil(WORKGROUP_SIZE);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("IBL Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("IBL Pass"),
+                timestamp_writes: None,
+            });
+
+            compute_pass.set_pipeline(&irradiance_pipeline);
+            compute_pass.set_bind_group(0, &irradiance_bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                workgroups(IRRADIANCE_SIZE),
+                workgroups(IRRADIANCE_SIZE),
+                6,
+            );
+
+            compute_pass.set_pipeline(&prefilter_pipeline);
+            for (mip, bind_group) in prefilter_bind_groups.iter().enumerate() {
+                let size = (PREFILTERED_SIZE >> mip).max(1);
+                compute_pass.set_bind_group(0, bind_group, &[]);
+                compute_pass.dispatch_workgroups(workgroups(size), workgroups(size), 6);
+            }
+
+            compute_pass.set_pipeline(&brdf_pipeline);
+            compute_pass.set_bind_group(0, &brdf_bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                workgroups(BRDF_LUT_SIZE),
+                workgroups(BRDF_LUT_SIZE),
+                1,
+            );
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Self::from_textures(device, irradiance, prefiltered, brdf_lut)
+    }
+
+    /// Hash of the environment's size, format and mip 0 texels, stored in the cache to tell which
+    /// environment it was generated from. Reads the texture back, so it needs `COPY_SRC`
+    pub fn source_hash(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        environment: &Texture,
+    ) -> Result<u64> {
+        let texture = &environment.texture;
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            bail!("the environment texture can't be read back without COPY_SRC");
+        }
+        let bytes_per_pixel = texture
+            .format()
+            .block_copy_size(None)
+            .filter(|_| texture.format().block_dimensions() == (1, 1))
+            .with_context(|| format!("can't read back {:?} textures", texture.format()))?;
+
+        let mut hasher = Fnv1a::default();
+        hasher.write(format!("{:?}", texture.format()).as_bytes());
+        for value in [
+            texture.width(),
+            texture.height(),
+            texture.depth_or_array_layers(),
+        ] {
+            hasher.write(&value.to_le_bytes());
+        }
+        hasher.write(&read_back_mip(device, queue, texture, 0, bytes_per_pixel)?);
+        Ok(hasher.0)
+    }
+
+    /// Loads maps written by `save`. Fails if they were generated from an environment with
+    /// another `source_hash`
+    pub fn load<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        source_hash: u64,
+        path: P,
+    ) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let data = bytes
+            .strip_prefix(CACHE_MAGIC)
+            .context("not an IBL cache file")?;
+        if data.len() < 8 {
+            bail!("IBL cache file is truncated");
+        }
+        let (hash, mut data) = data.split_at(8);
+        if hash != source_hash.to_le_bytes() {
+            bail!("IBL cache was generated from a different environment");
+        }
+
+        let (irradiance, prefiltered, brdf_lut) = Self::create_textures(device);
+        for texture in [&irradiance, &prefiltered, &brdf_lut] {
+            data = read_texture_data(queue, &texture.texture, data)?;
+        }
+        if !data.is_empty() {
+            bail!("IBL cache file has trailing data");
+        }
+
+        Ok(Self::from_textures(
+            device,
+            irradiance,
+            prefiltered,
+            brdf_lut,
+        ))
+    }
+
+    /// reads the maps back from the GPU and writes them to `path`, blocks until the copy is done
+    pub fn save<P: AsRef<Path>>(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        source_hash: u64,
+        path: P,
+    ) -> Result<()> {
+        let mut bytes = CACHE_MAGIC.to_vec();
+        bytes.extend_from_slice(&source_hash.to_le_bytes());
+        for texture in [&self.irradiance, &self.prefiltered, &self.brdf_lut] {
+            write_texture_data(device, queue, &texture.texture, &mut bytes)?;
+        }
+
+        if let Some(parent) = path.as_ref().parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// Loads the maps from `cache_path` if it exists and was generated from `environment`, otherwise
+    /// generates them and writes the cache. Cache failures only print a warning
+    pub fn load_or_generate<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        environment: &Texture,
+        cache_path: P,
+    ) -> Self {
+        let cache_path = cache_path.as_ref();
+        let source_hash = match Self::source_hash(device, queue, environment) {
+            Result::Ok(hash) => hash,
+            Err(e) => {
+                eprintln!("not caching IBL maps: {}", e);
+                return Self::generate(device, queue, environment);
+            }
+        };
+
+        if cache_path.exists() {
+            match Self::load(device, queue, source_hash, cache_path) {
+                Result::Ok(maps) => return maps,
+                Err(e) => eprintln!("couldn't load IBL cache {}: {}", cache_path.display(), e),
+            }
+        }
+
+        let maps = Self::generate(device, queue, environment);
+        if let Err(e) = maps.save(device, queue, source_hash, cache_path) {
+            eprintln!("couldn't write IBL cache {}: {}", cache_path.display(), e);
+        }
+        maps
+    }
+}
+
+/// WGSL declarations for the IBL bind group placed at bind group `group`.
+/// Prepend it to a shader source to get `ibl_ambient`.
+pub fn shader_snippet(group: u32) -> String {
+    include_str!("shaders/ibl.wgsl").replace("@group(0)", &format!("@group({})", group))
+}
+
+fn mip_size(texture: &wgpu::Texture, mip: u32) -> (u32, u32) {
+    (
+        (texture.width() >> mip).max(1),
+        (texture.height() >> mip).max(1),
+    )
+}
+
+// header: (width, layers, mips) as little endian u32, then every mip tightly packed
+fn write_texture_data(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    bytes: &mut Vec<u8>,
+) -> Result<()> {
+    let layers = texture.depth_or_array_layers();
+    for value in [texture.width(), layers, texture.mip_level_count()] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    for mip in 0..texture.mip_level_count() {
+        bytes.extend_from_slice(&read_back_mip(
+            device,
+            queue,
+            texture,
+            mip,
+            BYTES_PER_PIXEL,
+        )?);
+    }
+
+    Ok(())
+}
+
+// every layer of one mip tightly packed, blocks until the copy is done
+fn read_back_mip(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    mip: u32,
+    bytes_per_pixel: u32,
+) -> Result<Vec<u8>> {
+    let layers = texture.depth_or_array_layers();
+    let (width, height) = mip_size(texture, mip);
+    let unpadded_row = width * bytes_per_pixel;
+    let padded_row = unpadded_row.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("IBL Readback"),
+        size: (padded_row * height * layers) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("IBL Readback Encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: mip,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row),
+                rows_per_image: Some(height),
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: layers,
+        },
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    receiver.recv()??;
+
+    let mut bytes = Vec::with_capacity((unpadded_row * height * layers) as usize);
+    let mapped = slice.get_mapped_range();
+    for row in mapped.chunks(padded_row as usize) {
+        bytes.extend_from_slice(&row[..unpadded_row as usize]);
+    }
+    drop(mapped);
+    buffer.unmap();
+    Ok(bytes)
+}
+
+// 64 bit FNV-1a, unlike `DefaultHasher` it gives the same hash in every build
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+// uploads one texture written by `write_texture_data`, returns the rest of `data`
+fn read_texture_data<'a>(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    data: &'a [u8],
+) -> Result<&'a [u8]> {
+    let header: Vec<u32> = data
+        .chunks_exact(4)
+        .take(3)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect();
+    let layers = texture.depth_or_array_layers();
+    if header != [texture.width(), layers, texture.mip_level_count()] {
+        bail!("IBL cache was written with different sizes, delete it to regenerate");
+    }
+    let mut data = &data[12..];
+
+    for mip in 0..texture.mip_level_count() {
+        let (width, height) = mip_size(texture, mip);
+        let length = (width * height * layers * BYTES_PER_PIXEL) as usize;
+        if data.len() < length {
+            bail!("IBL cache file is truncated");
+        }
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture,
+                mip_level: mip,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &data[..length],
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * BYTES_PER_PIXEL),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: layers,
+            },
+        );
+        data = &data[length..];
+    }
+
+    Ok(data)
+}
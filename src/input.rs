@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+use winit::dpi::PhysicalPosition;
+use winit::event::{DeviceEvent, ElementState, KeyEvent, MouseButton, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+use crate::input_map::{self, Binding, InputMap};
+
+/// Keyboard and mouse state, kept up to date by `engine::run`.
+///
+/// "Just" pressed and released, the mouse delta and the scroll cover everything since the last
+/// frame, they are cleared after the render command runs. Losing focus releases everything held,
+/// since the release events go to another window.
+#[derive(Default)]
+pub struct Input {
+    keys_held: HashSet<KeyCode>,
+    keys_pressed: HashSet<KeyCode>,
+    keys_released: HashSet<KeyCode>,
+    buttons_held: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    cursor_position: Option<PhysicalPosition<f64>>,
+    mouse_delta: (f64, f64),
+    scroll: f32,
+}
+
+impl Input {
+    pub fn key_held(&self, key: KeyCode) -> bool {
+        self.keys_held.contains(&key)
+    }
+
+    pub fn key_just_pressed(&self, key: KeyCode) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    pub fn key_just_released(&self, key: KeyCode) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    pub fn button_held(&self, button: MouseButton) -> bool {
+        self.buttons_held.contains(&button)
+    }
+
+    pub fn button_just_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn button_just_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    /// in window pixels, `None` while the cursor is outside the window
+    pub fn cursor_position(&self) -> Option<PhysicalPosition<f64>> {
+        self.cursor_position
+    }
+
+    /// raw mouse motion this frame, keeps counting when the cursor hits the window edge
+    pub fn mouse_delta(&self) -> (f64, f64) {
+        self.mouse_delta
+    }
+
+    /// scroll lines this frame, positive with the wheel turned up
+    pub fn scroll(&self) -> f32 {
+        self.scroll
+    }
+
+    /// whether any binding of `action` is held, wheel bindings never are
+    pub fn action_held(&self, input_map: &InputMap, action: &str) -> bool {
+        input_map
+            .bindings_for(action)
+            .iter()
+            .any(|binding| match binding {
+                Binding::Key(key) => self.key_held(*key),
+                Binding::Mouse(button) => self.button_held(*button),
+                Binding::WheelUp | Binding::WheelDown => false,
+            })
+    }
+
+    /// whether a binding of `action` went down this frame, a wheel notch counts as a press
+    pub fn action_just_pressed(&self, input_map: &InputMap, action: &str) -> bool {
+        input_map
+            .bindings_for(action)
+            .iter()
+            .any(|binding| match binding {
+                Binding::Key(key) => self.key_just_pressed(*key),
+                Binding::Mouse(button) => self.button_just_pressed(*button),
+                Binding::WheelUp => self.scroll > 0.0,
+                Binding::WheelDown => self.scroll < 0.0,
+            })
+    }
+
+    pub fn action_just_released(&self, input_map: &InputMap, action: &str) -> bool {
+        input_map
+            .bindings_for(action)
+            .iter()
+            .any(|binding| match binding {
+                Binding::Key(key) => self.key_just_released(*key),
+                Binding::Mouse(button) => self.button_just_released(*button),
+                Binding::WheelUp | Binding::WheelDown => false,
+            })
+    }
+
+    pub fn process_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    self.keys_held.insert(*key);
+                    self.keys_pressed.insert(*key);
+                }
+                ElementState::Released => {
+                    self.keys_held.remove(key);
+                    self.keys_released.insert(*key);
+                }
+            },
+            WindowEvent::MouseInput { button, state, .. } => match state {
+                ElementState::Pressed => {
+                    self.buttons_held.insert(*button);
+                    self.buttons_pressed.insert(*button);
+                }
+                ElementState::Released => {
+                    self.buttons_held.remove(button);
+                    self.buttons_released.insert(*button);
+                }
+            },
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Some(*position);
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_position = None;
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                self.scroll += input_map::wheel_lines(delta);
+            }
+            WindowEvent::Focused(false) => {
+                self.keys_released.extend(self.keys_held.drain());
+                self.buttons_released.extend(self.buttons_held.drain());
+            }
+            _ => {}
+        }
+    }
+
+    pub fn process_device_event(&mut self, event: &DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.mouse_delta.0 += delta.0;
+            self.mouse_delta.1 += delta.1;
+        }
+    }
+
+    /// clears the per frame state, held keys and buttons stay
+    pub fn end_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll = 0.0;
+    }
+}
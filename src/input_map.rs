@@ -0,0 +1,608 @@
+use anyhow::*;
+use std::fmt;
+use std::path::Path;
+use winit::event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+// built-in actions
+pub const QUIT: &str = "quit";
+pub const TOGGLE_WIREFRAME: &str = "toggle_wireframe";
+/// hides the cursor and keeps it in the window, the camera turns with the mouse without a button held
+pub const TOGGLE_CURSOR_GRAB: &str = "toggle_cursor_grab";
+pub const MOVE_FORWARD: &str = "move_forward";
+pub const MOVE_BACKWARD: &str = "move_backward";
+pub const MOVE_LEFT: &str = "move_left";
+pub const MOVE_RIGHT: &str = "move_right";
+pub const MOVE_UP: &str = "move_up";
+pub const MOVE_DOWN: &str = "move_down";
+/// held to move the fly camera faster
+pub const MOVE_FAST: &str = "move_fast";
+/// held to move the fly camera slower
+pub const MOVE_SLOW: &str = "move_slow";
+/// held while dragging turns the camera
+pub const ROTATE_CAMERA: &str = "rotate_camera";
+/// held while dragging pans orbit and arcball cameras
+pub const PAN_CAMERA: &str = "pan_camera";
+
+// built-in axes
+/// scroll lines, positive with the wheel turned up
+pub const ZOOM: &str = "zoom";
+
+/// Something that can be bound to an action or axis
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+}
+
+impl Binding {
+    /// the binding a key, mouse button or wheel event is about, and whether it was pressed.
+    /// A wheel notch presses `WheelUp` or `WheelDown` and is never released
+    pub fn from_event(event: &WindowEvent) -> Option<(Binding, bool)> {
+        match event {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(key),
+                        state,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => Some((Binding::Key(*key), *state == ElementState::Pressed)),
+            WindowEvent::MouseInput { button, state, .. } => {
+                Some((Binding::Mouse(*button), *state == ElementState::Pressed))
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = wheel_lines(delta);
+                if lines > 0.0 {
+                    Some((Binding::WheelUp, true))
+                } else if lines < 0.0 {
+                    Some((Binding::WheelDown, true))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// parses the names `Display` writes, `KeyW`, `Space`, `MouseLeft`, `Mouse4`, `WheelUp`, ...
+    pub fn parse(name: &str) -> Option<Binding> {
+        match name {
+            "MouseLeft" => return Some(Binding::Mouse(MouseButton::Left)),
+            "MouseRight" => return Some(Binding::Mouse(MouseButton::Right)),
+            "MouseMiddle" => return Some(Binding::Mouse(MouseButton::Middle)),
+            "MouseBack" => return Some(Binding::Mouse(MouseButton::Back)),
+            "MouseForward" => return Some(Binding::Mouse(MouseButton::Forward)),
+            "WheelUp" => return Some(Binding::WheelUp),
+            "WheelDown" => return Some(Binding::WheelDown),
+            _ => {}
+        }
+
+        if let Some(button) = name.strip_prefix("Mouse") {
+            return button
+                .parse()
+                .ok()
+                .map(|id| Binding::Mouse(MouseButton::Other(id)));
+        }
+        KEY_CODES
+            .iter()
+            .find(|key| format!("{:?}", key) == name)
+            .map(|key| Binding::Key(*key))
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{:?}", key),
+            Binding::Mouse(MouseButton::Other(id)) => write!(f, "Mouse{}", id),
+            Binding::Mouse(button) => write!(f, "Mouse{:?}", button),
+            Binding::WheelUp => write!(f, "WheelUp"),
+            Binding::WheelDown => write!(f, "WheelDown"),
+        }
+    }
+}
+
+/// scroll amount in lines, pixel deltas count 100 pixels as a line
+pub fn wheel_lines(delta: &MouseScrollDelta) -> f32 {
+    match delta {
+        MouseScrollDelta::LineDelta(_, lines) => *lines,
+        MouseScrollDelta::PixelDelta(position) => position.y as f32 / 100.0,
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActionBinding {
+    pub action: String,
+    pub binding: Binding,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AxisBinding {
+    pub axis: String,
+    pub binding: Binding,
+    pub scale: f32,
+}
+
+/// Named actions and axes and what triggers them.
+///
+/// Actions are pressed and released, by keys, mouse buttons or a wheel notch. Axes take the
+/// wheel in lines times `scale`, a key or button bound to an axis counts as one line when pressed.
+/// A binding can serve several actions, `conflicts` lists where that happens so it can be
+/// rebound. The default map holds the engine's and the camera controllers' controls.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputMap {
+    pub action_list: Vec<ActionBinding>,
+    pub axis_list: Vec<AxisBinding>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let mut map = Self::empty();
+        map.bind_action(QUIT, Binding::Key(KeyCode::Escape));
+        map.bind_action(TOGGLE_WIREFRAME, Binding::Key(KeyCode::Tab));
+        map.bind_action(TOGGLE_CURSOR_GRAB, Binding::Key(KeyCode::KeyG));
+        map.bind_action(MOVE_FORWARD, Binding::Key(KeyCode::KeyW));
+        map.bind_action(MOVE_BACKWARD, Binding::Key(KeyCode::KeyS));
+        map.bind_action(MOVE_LEFT, Binding::Key(KeyCode::KeyA));
+        map.bind_action(MOVE_RIGHT, Binding::Key(KeyCode::KeyD));
+        map.bind_action(MOVE_UP, Binding::Key(KeyCode::Space));
+        map.bind_action(MOVE_DOWN, Binding::Key(KeyCode::ShiftLeft));
+        map.bind_action(MOVE_FAST, Binding::Key(KeyCode::ControlLeft));
+        map.bind_action(MOVE_SLOW, Binding::Key(KeyCode::AltLeft));
+        map.bind_action(ROTATE_CAMERA, Binding::Mouse(MouseButton::Left));
+        map.bind_action(PAN_CAMERA, Binding::Mouse(MouseButton::Middle));
+        map.bind_action(PAN_CAMERA, Binding::Mouse(MouseButton::Right));
+        map.bind_axis(ZOOM, Binding::WheelUp, 1.0);
+        map.bind_axis(ZOOM, Binding::WheelDown, -1.0);
+        map
+    }
+}
+
+impl InputMap {
+    /// a map without any bindings, not even the built-in ones
+    pub fn empty() -> Self {
+        Self {
+            action_list: vec![],
+            axis_list: vec![],
+        }
+    }
+
+    pub fn bind_action(&mut self, action: &str, binding: Binding) {
+        let item = ActionBinding {
+            action: action.to_string(),
+            binding,
+        };
+        if !self.action_list.contains(&item) {
+            self.action_list.push(item);
+        }
+    }
+
+    /// replaces everything bound to `action`
+    pub fn rebind_action(&mut self, action: &str, bindings: &[Binding]) {
+        self.unbind_action(action);
+        for binding in bindings {
+            self.bind_action(action, *binding);
+        }
+    }
+
+    pub fn unbind_action(&mut self, action: &str) {
+        self.action_list.retain(|x| x.action != action);
+    }
+
+    /// binds `binding` to `axis` with `scale`, replacing its earlier scale
+    pub fn bind_axis(&mut self, axis: &str, binding: Binding, scale: f32) {
+        self.axis_list
+            .retain(|x| x.axis != axis || x.binding != binding);
+        self.axis_list.push(AxisBinding {
+            axis: axis.to_string(),
+            binding,
+            scale,
+        });
+    }
+
+    pub fn unbind_axis(&mut self, axis: &str) {
+        self.axis_list.retain(|x| x.axis != axis);
+    }
+
+    /// removes `binding` from every action and axis
+    pub fn unbind(&mut self, binding: Binding) {
+        self.action_list.retain(|x| x.binding != binding);
+        self.axis_list.retain(|x| x.binding != binding);
+    }
+
+    pub fn actions_for(&self, binding: Binding) -> impl Iterator<Item = &str> {
+        self.action_list
+            .iter()
+            .filter(move |x| x.binding == binding)
+            .map(|x| x.action.as_str())
+    }
+
+    pub fn axes_for(&self, binding: Binding) -> impl Iterator<Item = (&str, f32)> {
+        self.axis_list
+            .iter()
+            .filter(move |x| x.binding == binding)
+            .map(|x| (x.axis.as_str(), x.scale))
+    }
+
+    pub fn bindings_for(&self, action: &str) -> Vec<Binding> {
+        self.action_list
+            .iter()
+            .filter(|x| x.action == action)
+            .map(|x| x.binding)
+            .collect()
+    }
+
+    /// whether `event` presses a binding of `action`
+    pub fn pressed(&self, action: &str, event: &WindowEvent) -> bool {
+        matches!(Binding::from_event(event), Some((binding, true)) if self.actions_for(binding).any(|x| x == action))
+    }
+
+    /// bindings used by more than one action or axis, with their names
+    pub fn conflicts(&self) -> Vec<(Binding, Vec<String>)> {
+        let mut conflicts: Vec<(Binding, Vec<String>)> = vec![];
+        let uses = self
+            .action_list
+            .iter()
+            .map(|x| (x.binding, &x.action))
+            .chain(self.axis_list.iter().map(|x| (x.binding, &x.axis)));
+
+        for (binding, name) in uses {
+            match conflicts.iter_mut().find(|(other, _)| *other == binding) {
+                Some((_, names)) => {
+                    if !names.contains(name) {
+                        names.push(name.clone());
+                    }
+                }
+                None => conflicts.push((binding, vec![name.clone()])),
+            }
+        }
+        conflicts.retain(|(_, names)| names.len() > 1);
+        conflicts
+    }
+
+    /// Reads the text format `Display` writes, one action or axis per line:
+    ///
+    /// ```text
+    /// # comment
+    /// action move_forward = KeyW, ArrowUp
+    /// axis zoom = WheelUp 1, WheelDown -1
+    /// ```
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut map = Self::empty();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (head, bindings) = line
+                .split_once('=')
+                .with_context(|| format!("line {}: expected `=`", number + 1))?;
+            let (kind, name) = head
+                .trim()
+                .split_once(char::is_whitespace)
+                .with_context(|| {
+                    format!(
+                        "line {}: expected `action` or `axis` and a name",
+                        number + 1
+                    )
+                })?;
+            let name = name.trim();
+
+            for entry in bindings.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+                let mut parts = entry.split_whitespace();
+                let binding_name = parts.next().unwrap_or_default();
+                let binding = Binding::parse(binding_name).with_context(|| {
+                    format!("line {}: unknown binding `{}`", number + 1, binding_name)
+                })?;
+
+                match kind {
+                    "action" => map.bind_action(name, binding),
+                    "axis" => {
+                        let scale = match parts.next() {
+                            Some(scale) => scale.parse().with_context(|| {
+                                format!("line {}: bad scale `{}`", number + 1, scale)
+                            })?,
+                            None => 1.0,
+                        };
+                        map.bind_axis(name, binding, scale);
+                    }
+                    _ => bail!(
+                        "line {}: expected `action` or `axis`, found `{}`",
+                        number + 1,
+                        kind
+                    ),
+                }
+            }
+        }
+
+        Ok(map)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("can't read input map {}", path.display()))?;
+        Self::parse(&text)
+    }
+
+    /// the map from `path`, or the default one when the file isn't there or doesn't parse
+    pub fn load_or_default<P: AsRef<Path>>(path: P) -> Self {
+        Self::load(path).unwrap_or_else(|error| {
+            eprintln!("{:#}, using the default input map", error);
+            Self::default()
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::write(path, self.to_string())?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for InputMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut written: Vec<&str> = vec![];
+        for item in &self.action_list {
+            if written.contains(&item.action.as_str()) {
+                continue;
+            }
+            written.push(&item.action);
+
+            let bindings: Vec<String> = self
+                .bindings_for(&item.action)
+                .iter()
+                .map(|binding| binding.to_string())
+                .collect();
+            writeln!(f, "action {} = {}", item.action, bindings.join(", "))?;
+        }
+
+        written.clear();
+        for item in &self.axis_list {
+            if written.contains(&item.axis.as_str()) {
+                continue;
+            }
+            written.push(&item.axis);
+
+            let bindings: Vec<String> = self
+                .axis_list
+                .iter()
+                .filter(|x| x.axis == item.axis)
+                .map(|x| format!("{} {}", x.binding, x.scale))
+                .collect();
+            writeln!(f, "axis {} = {}", item.axis, bindings.join(", "))?;
+        }
+
+        std::result::Result::Ok(())
+    }
+}
+
+// every `KeyCode` of winit 0.29, so whatever `Display` writes `Binding::parse` reads back
+const KEY_CODES: &[KeyCode] = &[
+    KeyCode::Backquote,
+    KeyCode::Backslash,
+    KeyCode::BracketLeft,
+    KeyCode::BracketRight,
+    KeyCode::Comma,
+    KeyCode::Digit0,
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+    KeyCode::Equal,
+    KeyCode::IntlBackslash,
+    KeyCode::IntlRo,
+    KeyCode::IntlYen,
+    KeyCode::KeyA,
+    KeyCode::KeyB,
+    KeyCode::KeyC,
+    KeyCode::KeyD,
+    KeyCode::KeyE,
+    KeyCode::KeyF,
+    KeyCode::KeyG,
+    KeyCode::KeyH,
+    KeyCode::KeyI,
+    KeyCode::KeyJ,
+    KeyCode::KeyK,
+    KeyCode::KeyL,
+    KeyCode::KeyM,
+    KeyCode::KeyN,
+    KeyCode::KeyO,
+    KeyCode::KeyP,
+    KeyCode::KeyQ,
+    KeyCode::KeyR,
+    KeyCode::KeyS,
+    KeyCode::KeyT,
+    KeyCode::KeyU,
+    KeyCode::KeyV,
+    KeyCode::KeyW,
+    KeyCode::KeyX,
+    KeyCode::KeyY,
+    KeyCode::KeyZ,
+    KeyCode::Minus,
+    KeyCode::Period,
+    KeyCode::Quote,
+    KeyCode::Semicolon,
+    KeyCode::Slash,
+    KeyCode::AltLeft,
+    KeyCode::AltRight,
+    KeyCode::Backspace,
+    KeyCode::CapsLock,
+    KeyCode::ContextMenu,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::Enter,
+    KeyCode::SuperLeft,
+    KeyCode::SuperRight,
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::Space,
+    KeyCode::Tab,
+    KeyCode::Convert,
+    KeyCode::KanaMode,
+    KeyCode::Lang1,
+    KeyCode::Lang2,
+    KeyCode::Lang3,
+    KeyCode::Lang4,
+    KeyCode::Lang5,
+    KeyCode::NonConvert,
+    KeyCode::Delete,
+    KeyCode::End,
+    KeyCode::Help,
+    KeyCode::Home,
+    KeyCode::Insert,
+    KeyCode::PageDown,
+    KeyCode::PageUp,
+    KeyCode::ArrowDown,
+    KeyCode::ArrowLeft,
+    KeyCode::ArrowRight,
+    KeyCode::ArrowUp,
+    KeyCode::NumLock,
+    KeyCode::Numpad0,
+    KeyCode::Numpad1,
+    KeyCode::Numpad2,
+    KeyCode::Numpad3,
+    KeyCode::Numpad4,
+    KeyCode::Numpad5,
+    KeyCode::Numpad6,
+    KeyCode::Numpad7,
+    KeyCode::Numpad8,
+    KeyCode::Numpad9,
+    KeyCode::NumpadAdd,
+    KeyCode::NumpadBackspace,
+    KeyCode::NumpadClear,
+    KeyCode::NumpadClearEntry,
+    KeyCode::NumpadComma,
+    KeyCode::NumpadDecimal,
+    KeyCode::NumpadDivide,
+    KeyCode::NumpadEnter,
+    KeyCode::NumpadEqual,
+    KeyCode::NumpadHash,
+    KeyCode::NumpadMemoryAdd,
+    KeyCode::NumpadMemoryClear,
+    KeyCode::NumpadMemoryRecall,
+    KeyCode::NumpadMemoryStore,
+    KeyCode::NumpadMemorySubtract,
+    KeyCode::NumpadMultiply,
+    KeyCode::NumpadParenLeft,
+    KeyCode::NumpadParenRight,
+    KeyCode::NumpadStar,
+    KeyCode::NumpadSubtract,
+    KeyCode::Escape,
+    KeyCode::Fn,
+    KeyCode::FnLock,
+    KeyCode::PrintScreen,
+    KeyCode::ScrollLock,
+    KeyCode::Pause,
+    KeyCode::BrowserBack,
+    KeyCode::BrowserFavorites,
+    KeyCode::BrowserForward,
+    KeyCode::BrowserHome,
+    KeyCode::BrowserRefresh,
+    KeyCode::BrowserSearch,
+    KeyCode::BrowserStop,
+    KeyCode::Eject,
+    KeyCode::LaunchApp1,
+    KeyCode::LaunchApp2,
+    KeyCode::LaunchMail,
+    KeyCode::MediaPlayPause,
+    KeyCode::MediaSelect,
+    KeyCode::MediaStop,
+    KeyCode::MediaTrackNext,
+    KeyCode::MediaTrackPrevious,
+    KeyCode::Power,
+    KeyCode::Sleep,
+    KeyCode::AudioVolumeDown,
+    KeyCode::AudioVolumeMute,
+    KeyCode::AudioVolumeUp,
+    KeyCode::WakeUp,
+    KeyCode::Meta,
+    KeyCode::Hyper,
+    KeyCode::Turbo,
+    KeyCode::Abort,
+    KeyCode::Resume,
+    KeyCode::Suspend,
+    KeyCode::Again,
+    KeyCode::Copy,
+    KeyCode::Cut,
+    KeyCode::Find,
+    KeyCode::Open,
+    KeyCode::Paste,
+    KeyCode::Props,
+    KeyCode::Select,
+    KeyCode::Undo,
+    KeyCode::Hiragana,
+    KeyCode::Katakana,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::F13,
+    KeyCode::F14,
+    KeyCode::F15,
+    KeyCode::F16,
+    KeyCode::F17,
+    KeyCode::F18,
+    KeyCode::F19,
+    KeyCode::F20,
+    KeyCode::F21,
+    KeyCode::F22,
+    KeyCode::F23,
+    KeyCode::F24,
+    KeyCode::F25,
+    KeyCode::F26,
+    KeyCode::F27,
+    KeyCode::F28,
+    KeyCode::F29,
+    KeyCode::F30,
+    KeyCode::F31,
+    KeyCode::F32,
+    KeyCode::F33,
+    KeyCode::F34,
+    KeyCode::F35,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_round_trips_through_parse() {
+        let mut map = InputMap::default();
+        map.bind_action("screenshot", Binding::Key(KeyCode::PrintScreen));
+        map.bind_action("screenshot", Binding::Key(KeyCode::F13));
+        map.bind_action("pause", Binding::Key(KeyCode::Pause));
+        map.bind_axis("zoom", Binding::Key(KeyCode::NumpadDecimal), 0.5);
+        map.bind_action("next_weapon", Binding::Mouse(MouseButton::Other(7)));
+
+        let parsed = InputMap::parse(&map.to_string()).unwrap();
+        assert_eq!(parsed, map);
+    }
+
+    #[test]
+    fn every_key_parses_by_its_name() {
+        for key in KEY_CODES {
+            let binding = Binding::Key(*key);
+            assert_eq!(Binding::parse(&binding.to_string()), Some(binding));
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use anyhow::*;
+use ktx2::{
+    BasicDataFormatDescriptor, ColorModel, DataFormatDescriptorHeader, Format,
+    SupercompressionScheme,
+};
+use std::io::Read;
+
+use crate::sampler::SamplerManager;
+use crate::texture::{Texture, TextureOptions};
+
+/// Block compression families, each needs its wgpu feature on the device
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// BC1-7, desktop GPUs
+    Bc,
+    /// ASTC LDR, most mobile GPUs and Apple silicon
+    Astc,
+    /// ETC2/EAC, OpenGL ES 3 class GPUs
+    Etc2,
+}
+
+impl Compression {
+    /// in order of preference
+    pub const ALL: [Compression; 3] = [Compression::Bc, Compression::Astc, Compression::Etc2];
+
+    pub fn feature(self) -> wgpu::Features {
+        match self {
+            Compression::Bc => wgpu::Features::TEXTURE_COMPRESSION_BC,
+            Compression::Astc => wgpu::Features::TEXTURE_COMPRESSION_ASTC,
+            Compression::Etc2 => wgpu::Features::TEXTURE_COMPRESSION_ETC2,
+        }
+    }
+
+    /// the families `features` allows, most preferred first
+    pub fn supported(features: wgpu::Features) -> Vec<Compression> {
+        Self::ALL
+            .into_iter()
+            .filter(|compression| features.contains(compression.feature()))
+            .collect()
+    }
+}
+
+/// wgpu format for a KTX2 format, `None` for formats wgpu has no equivalent for
+pub fn wgpu_format(format: Format) -> Option<wgpu::TextureFormat> {
+    use wgpu::TextureFormat as F;
+
+    let astc_start = Format::ASTC_4x4_UNORM_BLOCK.0.get();
+    let astc_end = Format::ASTC_12x12_SRGB_BLOCK.0.get();
+    if (astc_start..=astc_end).contains(&format.0.get()) {
+        use wgpu::AstcBlock::*;
+        // unorm and sRGB pairs in block size order
+        let index = format.0.get() - astc_start;
+        let block = [
+            B4x4, B5x4, B5x5, B6x5, B6x6, B8x5, B8x6, B8x8, B10x5, B10x6, B10x8, B10x10, B12x10,
+            B12x12,
+        ][index as usize / 2];
+        let channel = if index % 2 == 1 {
+            wgpu::AstcChannel::UnormSrgb
+        } else {
+            wgpu::AstcChannel::Unorm
+        };
+        return Some(F::Astc { block, channel });
+    }
+
+    Some(match format {
+        Format::R8_UNORM => F::R8Unorm,
+        Format::R8G8_UNORM => F::Rg8Unorm,
+        Format::R8G8B8A8_UNORM => F::Rgba8Unorm,
+        Format::R8G8B8A8_SRGB => F::Rgba8UnormSrgb,
+        Format::B8G8R8A8_UNORM => F::Bgra8Unorm,
+        Format::B8G8R8A8_SRGB => F::Bgra8UnormSrgb,
+        Format::R16_SFLOAT => F::R16Float,
+        Format::R16G16_SFLOAT => F::Rg16Float,
+        Format::R16G16B16A16_SFLOAT => F::Rgba16Float,
+        Format::R32_SFLOAT => F::R32Float,
+        Format::R32G32B32A32_SFLOAT => F::Rgba32Float,
+        Format::B10G11R11_UFLOAT_PACK32 => F::Rg11b10Float,
+        Format::E5B9G9R9_UFLOAT_PACK32 => F::Rgb9e5Ufloat,
+        // BC1 without alpha decodes with alpha 1
+        Format::BC1_RGB_UNORM_BLOCK | Format::BC1_RGBA_UNORM_BLOCK => F::Bc1RgbaUnorm,
+        Format::BC1_RGB_SRGB_BLOCK | Format::BC1_RGBA_SRGB_BLOCK => F::Bc1RgbaUnormSrgb,
+        Format::BC2_UNORM_BLOCK => F::Bc2RgbaUnorm,
+        Format::BC2_SRGB_BLOCK => F::Bc2RgbaUnormSrgb,
+        Format::BC3_UNORM_BLOCK => F::Bc3RgbaUnorm,
+        Format::BC3_SRGB_BLOCK => F::Bc3RgbaUnormSrgb,
+        Format::BC4_UNORM_BLOCK => F::Bc4RUnorm,
+        Format::BC4_SNORM_BLOCK => F::Bc4RSnorm,
+        Format::BC5_UNORM_BLOCK => F::Bc5RgUnorm,
+        Format::BC5_SNORM_BLOCK => F::Bc5RgSnorm,
+        Format::BC6H_UFLOAT_BLOCK => F::Bc6hRgbUfloat,
+        Format::BC6H_SFLOAT_BLOCK => F::Bc6hRgbFloat,
+        Format::BC7_UNORM_BLOCK => F::Bc7RgbaUnorm,
+        Format::BC7_SRGB_BLOCK => F::Bc7RgbaUnormSrgb,
+        Format::ETC2_R8G8B8_UNORM_BLOCK => F::Etc2Rgb8Unorm,
+        Format::ETC2_R8G8B8_SRGB_BLOCK => F::Etc2Rgb8UnormSrgb,
+        Format::ETC2_R8G8B8A1_UNORM_BLOCK => F::Etc2Rgb8A1Unorm,
+        Format::ETC2_R8G8B8A1_SRGB_BLOCK => F::Etc2Rgb8A1UnormSrgb,
+        Format::ETC2_R8G8B8A8_UNORM_BLOCK => F::Etc2Rgba8Unorm,
+        Format::ETC2_R8G8B8A8_SRGB_BLOCK => F::Etc2Rgba8UnormSrgb,
+        Format::EAC_R11_UNORM_BLOCK => F::EacR11Unorm,
+        Format::EAC_R11_SNORM_BLOCK => F::EacR11Snorm,
+        Format::EAC_R11G11_UNORM_BLOCK => F::EacRg11Unorm,
+        Format::EAC_R11G11_SNORM_BLOCK => F::EacRg11Snorm,
+        _ => return None,
+    })
+}
+
+fn basic_descriptor<'a>(reader: &'a ktx2::Reader<&[u8]>) -> Option<BasicDataFormatDescriptor<'a>> {
+    reader
+        .data_format_descriptors()
+        .find(|descriptor| descriptor.header == DataFormatDescriptorHeader::BASIC)
+        .and_then(|descriptor| BasicDataFormatDescriptor::parse(descriptor.data).ok())
+}
+
+// Basis Universal payloads have no format of their own and get transcoded
+fn is_uastc(reader: &ktx2::Reader<&[u8]>) -> bool {
+    reader.header().format.is_none()
+        && basic_descriptor(reader)
+            .is_some_and(|descriptor| descriptor.color_model == Some(ColorModel::UASTC))
+}
+
+#[cfg(feature = "basis")]
+fn is_srgb(reader: &ktx2::Reader<&[u8]>) -> bool {
+    use ktx2::TransferFunction;
+
+    basic_descriptor(reader)
+        .is_some_and(|descriptor| descriptor.transfer_function == Some(TransferFunction::SRGB))
+}
+
+/// whether `bytes` can be uploaded to a device with `features`, without transcoding to an uncompressed format
+pub fn is_supported(bytes: &[u8], features: wgpu::Features) -> bool {
+    let Result::Ok(reader) = ktx2::Reader::new(bytes) else {
+        return false;
+    };
+    if is_uastc(&reader) {
+        return cfg!(feature = "basis") && !Compression::supported(features).is_empty();
+    }
+
+    reader
+        .header()
+        .format
+        .and_then(wgpu_format)
+        .is_some_and(|format| features.contains(format.required_features()))
+}
+
+struct Payload {
+    format: wgpu::TextureFormat,
+    levels: Vec<Vec<u8>>,
+}
+
+fn decode(reader: &ktx2::Reader<&[u8]>, features: wgpu::Features) -> Result<Payload> {
+    let header = reader.header();
+
+    let mut levels = vec![];
+    for level in reader.levels() {
+        levels.push(match header.supercompression_scheme {
+            None => level.to_vec(),
+            Some(SupercompressionScheme::Zstandard) => {
+                let mut cursor = level;
+                let mut decoder = ruzstd::StreamingDecoder::new(&mut cursor)
+                    .map_err(|e| anyhow!("invalid zstd level data: {}", e))?;
+                let mut decompressed = vec![];
+                decoder.read_to_end(&mut decompressed)?;
+                decompressed
+            }
+            Some(SupercompressionScheme::BasisLZ) => bail!(
+                "ETC1S/BasisLZ Basis Universal payloads aren't supported, encode the KTX2 file as UASTC"
+            ),
+            Some(scheme) => bail!("unsupported KTX2 supercompression {:?}", scheme),
+        });
+    }
+
+    if is_uastc(reader) {
+        return transcode_uastc(reader, levels, features);
+    }
+
+    let Some(format) = header.format else {
+        bail!("KTX2 file without a format, only UASTC Basis Universal payloads are supported");
+    };
+    let format =
+        wgpu_format(format).with_context(|| format!("unsupported KTX2 format {:?}", format))?;
+    Ok(Payload { format, levels })
+}
+
+#[cfg(not(feature = "basis"))]
+fn transcode_uastc(
+    _reader: &ktx2::Reader<&[u8]>,
+    _levels: Vec<Vec<u8>>,
+    _features: wgpu::Features,
+) -> Result<Payload> {
+    bail!("Basis Universal KTX2 file, built without the `basis` feature")
+}
+
+/// transcodes to the best compressed format `features` allows, or to RGBA8 if there is none
+#[cfg(feature = "basis")]
+fn transcode_uastc(
+    reader: &ktx2::Reader<&[u8]>,
+    levels: Vec<Vec<u8>>,
+    features: wgpu::Features,
+) -> Result<Payload> {
+    use basis_universal::{
+        DecodeFlags, LowLevelUastcTranscoder, SliceParametersUastc, TranscoderBlockFormat,
+    };
+
+    let srgb = is_srgb(reader);
+    let (block_format, format) = match Compression::supported(features).first() {
+        Some(Compression::Bc) => (
+            TranscoderBlockFormat::BC7,
+            wgpu::TextureFormat::Bc7RgbaUnorm,
+        ),
+        Some(Compression::Astc) => (
+            TranscoderBlockFormat::ASTC_4x4,
+            wgpu::TextureFormat::Astc {
+                block: wgpu::AstcBlock::B4x4,
+                channel: wgpu::AstcChannel::Unorm,
+            },
+        ),
+        Some(Compression::Etc2) => (
+            TranscoderBlockFormat::ETC2_RGBA,
+            wgpu::TextureFormat::Etc2Rgba8Unorm,
+        ),
+        None => (
+            TranscoderBlockFormat::RGBA32,
+            wgpu::TextureFormat::Rgba8Unorm,
+        ),
+    };
+    let format = if srgb {
+        format.add_srgb_suffix()
+    } else {
+        format
+    };
+
+    basis_universal::transcoder_init();
+    let transcoder = LowLevelUastcTranscoder::new();
+    let header = reader.header();
+    let slices = header.layer_count.max(1) * header.face_count;
+
+    let mut transcoded = vec![];
+    for (level, data) in levels.iter().enumerate() {
+        let width = (header.pixel_width >> level).max(1);
+        let height = (header.pixel_height >> level).max(1);
+        let blocks_x = width.div_ceil(4);
+        let blocks_y = height.div_ceil(4);
+        // UASTC blocks are 16 bytes
+        let slice_size = (blocks_x * blocks_y * 16) as usize;
+        if data.len() != slice_size * slices as usize {
+            bail!(
+                "UASTC level {} has {} bytes, expected {} for {} slices",
+                level,
+                data.len(),
+                slice_size * slices as usize,
+                slices
+            );
+        }
+
+        let mut level_data = vec![];
+        for slice in data.chunks_exact(slice_size) {
+            let slice_data = transcoder
+                .transcode_slice(
+                    slice,
+                    SliceParametersUastc {
+                        num_blocks_x: blocks_x,
+                        num_blocks_y: blocks_y,
+                        has_alpha: true,
+                        original_width: width,
+                        original_height: height,
+                    },
+                    DecodeFlags::HIGH_QUALITY,
+                    block_format,
+                )
+                .map_err(|e| anyhow!("couldn't transcode UASTC level {}: {:?}", level, e))?;
+            level_data.extend_from_slice(&slice_data);
+        }
+        transcoded.push(level_data);
+    }
+
+    Ok(Payload {
+        format,
+        levels: transcoded,
+    })
+}
+
+impl Texture {
+    /// Uploads a KTX2 file with the mip levels it contains. Block compressed formats need the matching
+    /// `Compression` feature on the device, UASTC Basis Universal payloads are transcoded to the best one it has.
+    /// `options.mipmaps` and `options.color_space` don't apply, both come from the file
+    pub fn from_ktx2(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        options: &TextureOptions,
+        samplers: &mut SamplerManager,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let reader = ktx2::Reader::new(bytes).map_err(|e| anyhow!("invalid KTX2 file: {:?}", e))?;
+        let header = reader.header();
+        if header.pixel_depth > 1 {
+            bail!("3D KTX2 textures aren't supported");
+        }
+
+        let payload = decode(&reader, device.features())?;
+        let format = payload.format;
+        if !device.features().contains(format.required_features()) {
+            bail!(
+                "{:?} needs {:?}, which the device doesn't have",
+                format,
+                format.required_features()
+            );
+        }
+
+        let (block_width, block_height) = format.block_dimensions();
+        if header.pixel_width % block_width != 0 || header.pixel_height.max(1) % block_height != 0 {
+            bail!(
+                "{}x{} isn't a multiple of the {}x{} blocks of {:?}",
+                header.pixel_width,
+                header.pixel_height,
+                block_width,
+                block_height,
+                format
+            );
+        }
+
+        let layers = header.layer_count.max(1);
+        let size = wgpu::Extent3d {
+            width: header.pixel_width,
+            height: header.pixel_height.max(1),
+            depth_or_array_layers: layers * header.face_count,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: payload.levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let block_size = format.block_copy_size(None).unwrap();
+        for (level, data) in payload.levels.iter().enumerate() {
+            // compressed mips are padded to whole blocks
+            let physical_size = size
+                .mip_level_size(level as u32, wgpu::TextureDimension::D2)
+                .physical_size(format);
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(physical_size.width / block_width * block_size),
+                    rows_per_image: Some(physical_size.height / block_height),
+                },
+                physical_size,
+            );
+        }
+
+        let dimension = match (header.face_count, layers) {
+            (6, 1) => wgpu::TextureViewDimension::Cube,
+            (6, _) => wgpu::TextureViewDimension::CubeArray,
+            (_, 1) => wgpu::TextureViewDimension::D2,
+            _ => wgpu::TextureViewDimension::D2Array,
+        };
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(dimension),
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler: samplers.get(device, &options.sampler),
+        })
+    }
+
+    /// Picks the first of several encodings of the same texture that the device can use, e.g. BC7, ASTC and
+    /// ETC2 versions followed by an uncompressed RGBA8 one as the fallback that always works
+    pub fn from_ktx2_variants(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        variants: &[&[u8]],
+        options: &TextureOptions,
+        samplers: &mut SamplerManager,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let features = device.features();
+        // a UASTC payload works everywhere, transcoded to RGBA8 at worst
+        let variant = variants
+            .iter()
+            .find(|bytes| is_supported(bytes, features))
+            .or_else(|| {
+                variants.iter().find(|bytes| {
+                    ktx2::Reader::new(**bytes)
+                        .is_ok_and(|reader| cfg!(feature = "basis") && is_uastc(&reader))
+                })
+            })
+            .context(
+                "none of the KTX2 variants is supported by the device, add an uncompressed one",
+            )?;
+
+        Self::from_ktx2(device, queue, variant, options, samplers, label)
+    }
+}
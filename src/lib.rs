@@ -1,7 +1,26 @@
+pub mod antialias;
+pub mod atlas;
+pub mod bloom;
 pub mod camera;
+pub mod camera_path;
+pub mod culling;
 pub mod engine;
+pub mod gpu_culling;
+pub mod hdr;
+pub mod ibl;
+pub mod input;
+pub mod input_map;
+#[cfg(feature = "ktx2")]
+pub mod ktx;
+pub mod light;
 pub mod object;
+pub mod orbit;
 pub mod pipeline;
+pub mod render_target;
 pub mod renderer;
+pub mod sampler;
 pub mod scene;
+pub mod shadow;
+pub mod skybox;
 pub mod texture;
+pub mod viewport;
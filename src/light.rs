@@ -0,0 +1,196 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::*;
+
+pub const MAX_LIGHTS: usize = 8;
+
+// matches the `kind` constants in shaders/lighting.wgsl
+const LIGHT_KIND_DIRECTIONAL: f32 = 0.0;
+const LIGHT_KIND_SPOT: f32 = 1.0;
+const LIGHT_KIND_POINT: f32 = 2.0;
+
+pub struct DirectionalLight {
+    pub direction: Vector3<f32>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+impl Default for DirectionalLight {
+    fn default() -> Self {
+        Self {
+            direction: Vector3::new(-0.5, -1.0, -0.3),
+            color: [1.0; 3],
+            intensity: 1.0,
+        }
+    }
+}
+
+pub struct SpotLight {
+    pub position: Point3<f32>,
+    pub direction: Vector3<f32>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+    pub inner_angle: Rad<f32>,
+    pub outer_angle: Rad<f32>,
+}
+
+impl Default for SpotLight {
+    fn default() -> Self {
+        Self {
+            position: Point3::new(0.0, 5.0, 0.0),
+            direction: -Vector3::unit_y(),
+            color: [1.0; 3],
+            intensity: 1.0,
+            range: 20.0,
+            inner_angle: Deg(20.0).into(),
+            outer_angle: Deg(30.0).into(),
+        }
+    }
+}
+
+pub struct PointLight {
+    pub position: Point3<f32>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+}
+
+impl Default for PointLight {
+    fn default() -> Self {
+        Self {
+            position: Point3::new(0.0, 2.0, 0.0),
+            color: [1.0; 3],
+            intensity: 1.0,
+            range: 10.0,
+        }
+    }
+}
+
+pub enum LightKind {
+    Directional(DirectionalLight),
+    Spot(SpotLight),
+    Point(PointLight),
+}
+
+pub struct Light {
+    pub kind: LightKind,
+    pub cast_shadow: bool,
+}
+
+impl Light {
+    pub fn directional(light: DirectionalLight) -> Self {
+        Self {
+            kind: LightKind::Directional(light),
+            cast_shadow: true,
+        }
+    }
+
+    pub fn spot(light: SpotLight) -> Self {
+        Self {
+            kind: LightKind::Spot(light),
+            cast_shadow: true,
+        }
+    }
+
+    pub fn point(light: PointLight) -> Self {
+        Self {
+            kind: LightKind::Point(light),
+            cast_shadow: true,
+        }
+    }
+
+    /// `shadow_index` is the first shadow map layer of this light (the cube index for point lights),
+    /// or -1 if it has none
+    pub fn to_raw(&self, shadow_index: i32) -> LightRaw {
+        match &self.kind {
+            LightKind::Directional(light) => {
+                let direction = light.direction.normalize();
+                LightRaw {
+                    position: [0.0, 0.0, 0.0, LIGHT_KIND_DIRECTIONAL],
+                    direction: [direction.x, direction.y, direction.z, shadow_index as f32],
+                    color: [
+                        light.color[0],
+                        light.color[1],
+                        light.color[2],
+                        light.intensity,
+                    ],
+                    params: [0.0; 4],
+                }
+            }
+            LightKind::Spot(light) => {
+                let direction = light.direction.normalize();
+                LightRaw {
+                    position: [
+                        light.position.x,
+                        light.position.y,
+                        light.position.z,
+                        LIGHT_KIND_SPOT,
+                    ],
+                    direction: [direction.x, direction.y, direction.z, shadow_index as f32],
+                    color: [
+                        light.color[0],
+                        light.color[1],
+                        light.color[2],
+                        light.intensity,
+                    ],
+                    params: [
+                        light.range,
+                        light.inner_angle.0.cos(),
+                        light.outer_angle.0.cos(),
+                        0.0,
+                    ],
+                }
+            }
+            LightKind::Point(light) => LightRaw {
+                position: [
+                    light.position.x,
+                    light.position.y,
+                    light.position.z,
+                    LIGHT_KIND_POINT,
+                ],
+                direction: [0.0, 0.0, 0.0, shadow_index as f32],
+                color: [
+                    light.color[0],
+                    light.color[1],
+                    light.color[2],
+                    light.intensity,
+                ],
+                params: [light.range, 0.0, 0.0, 0.0],
+            },
+        }
+    }
+}
+
+/// position.w: light kind, direction.w: shadow index, color.w: intensity,
+/// params: (range, cos inner angle, cos outer angle, unused)
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct LightRaw {
+    pub position: [f32; 4],
+    pub direction: [f32; 4],
+    pub color: [f32; 4],
+    pub params: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct LightsUniform {
+    pub lights: [LightRaw; MAX_LIGHTS],
+    pub count: u32,
+    _padding: [u32; 3],
+}
+
+impl Default for LightsUniform {
+    fn default() -> Self {
+        Self::zeroed()
+    }
+}
+
+impl LightsUniform {
+    pub fn push(&mut self, light: LightRaw) {
+        if (self.count as usize) < MAX_LIGHTS {
+            self.lights[self.count as usize] = light;
+            self.count += 1;
+        }
+    }
+}
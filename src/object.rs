@@ -1,11 +1,30 @@
+use anyhow::*;
 use bytemuck::{Pod, Zeroable};
+use russimp::material::PropertyTypeInfo;
+use russimp::texture::TextureType;
+use std::path::Path;
 use wgpu::util::DeviceExt;
 
+use crate::culling::Aabb;
+use crate::sampler::SamplerManager;
+use crate::texture::{ColorSpace, Texture, TextureOptions};
+
 pub struct Object {
     pub mesh: Mesh,
     pub position: [f32; 3],
 }
 
+impl Object {
+    pub fn model_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::from_translation(self.position.into())
+    }
+
+    /// the mesh bounds in world space
+    pub fn world_bounds(&self) -> Aabb {
+        self.mesh.bounds.transform(&self.model_matrix())
+    }
+}
+
 pub type IndexType = u16;
 
 pub struct Mesh {
@@ -13,6 +32,8 @@ pub struct Mesh {
     pub indices: Vec<IndexType>,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
+    /// bounds of the vertices in model space
+    pub bounds: Aabb,
 }
 
 impl Mesh {
@@ -30,6 +51,7 @@ impl Mesh {
         });
 
         Self {
+            bounds: Aabb::from_vertices(&vertices),
             vertices,
             indices,
             vertex_buffer,
@@ -100,6 +122,95 @@ pub fn get_mesh_from_model(device: &wgpu::Device, file_path: &str, scale: f32) -
     Mesh::create_mesh(device, vertices, indices)
 }
 
+/// what a material uses a texture for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureSlot {
+    BaseColor,
+    Emissive,
+    Normal,
+    MetallicRoughness,
+    Occlusion,
+    Other,
+}
+
+impl TextureSlot {
+    fn from_texture_type(texture_type: &TextureType) -> Self {
+        match texture_type {
+            TextureType::Diffuse | TextureType::BaseColor => TextureSlot::BaseColor,
+            TextureType::Emissive | TextureType::EmissionColor => TextureSlot::Emissive,
+            TextureType::Normals | TextureType::NormalCamera => TextureSlot::Normal,
+            // assimp reports glTF's metallicRoughness texture as unknown
+            TextureType::Metalness | TextureType::Roughness | TextureType::Unknown => TextureSlot::MetallicRoughness,
+            // and the occlusion texture as a light map
+            TextureType::AmbientOcclusion | TextureType::LightMap => TextureSlot::Occlusion,
+            _ => TextureSlot::Other,
+        }
+    }
+
+    /// only color maps are stored as sRGB, everything else is data that must not be decoded
+    pub fn color_space(self) -> ColorSpace {
+        match self {
+            TextureSlot::BaseColor | TextureSlot::Emissive => ColorSpace::Srgb,
+            _ => ColorSpace::Linear,
+        }
+    }
+}
+
+pub struct ModelTexture {
+    pub material: usize,
+    pub slot: TextureSlot,
+    pub texture: Texture,
+}
+
+/// Loads the textures the model's materials reference, with the color space their slot needs.
+/// `options.color_space` is ignored, the rest applies to every texture. Paths are relative to the
+/// model file, embedded textures aren't supported and are skipped.
+pub fn get_textures_from_model(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    file_path: &str,
+    options: &TextureOptions,
+    samplers: &mut SamplerManager,
+) -> Result<Vec<ModelTexture>> {
+    let model_scene = russimp::scene::Scene::from_file(file_path, vec![])
+        .map_err(|e| anyhow!("couldn't import {}: {:?}", file_path, e))?;
+    let directory = Path::new(file_path).parent().unwrap_or(Path::new(""));
+
+    let mut textures: Vec<ModelTexture> = vec![];
+    for (material, properties) in model_scene.materials.iter().map(|m| &m.properties).enumerate() {
+        for property in properties {
+            let PropertyTypeInfo::String(path) = &property.data else {
+                continue;
+            };
+            if property.key != "$tex.file" {
+                continue;
+            }
+            if path.starts_with('*') {
+                eprintln!("skipping embedded texture {} in {}", path, file_path);
+                continue;
+            }
+
+            let slot = TextureSlot::from_texture_type(&property.semantic);
+            // metallic and roughness usually point at the same image
+            if textures.iter().any(|t| t.material == material && t.slot == slot) {
+                continue;
+            }
+
+            let img = image::open(directory.join(path))
+                .with_context(|| format!("couldn't load texture {} of {}", path, file_path))?;
+            let texture_options = TextureOptions {
+                color_space: slot.color_space(),
+                ..*options
+            };
+            let texture = Texture::from_image_with_options(device, queue, &img, &texture_options, samplers, Some(path))?;
+
+            textures.push(ModelTexture { material, slot, texture });
+        }
+    }
+
+    Ok(textures)
+}
+
 pub fn get_object_from_mesh(mesh: Mesh, position: [f32; 3]) -> Object {
     Object{
         mesh,
@@ -0,0 +1,305 @@
+use cgmath::*;
+
+use crate::camera::{Camera, Projection, ProjectionKind, SAFE_FRAC_PI_2};
+use crate::input_map;
+
+// rotation per pixel dragged at sensitivity 1
+const RADIANS_PER_PIXEL: f32 = 0.005;
+
+// Drag state and motion collected between updates, shared by the orbit and arcball controllers
+struct Drag {
+    rotating: bool,
+    panning: bool,
+    rotate: Vector2<f32>,
+    pan: Vector2<f32>,
+    // scroll lines, positive towards the target
+    scroll: f32,
+}
+
+impl Default for Drag {
+    fn default() -> Self {
+        Self {
+            rotating: false,
+            panning: false,
+            rotate: Vector2::zero(),
+            pan: Vector2::zero(),
+            scroll: 0.0,
+        }
+    }
+}
+
+impl Drag {
+    fn process_action(&mut self, action: &str, pressed: bool) -> bool {
+        match action {
+            input_map::ROTATE_CAMERA => self.rotating = pressed,
+            input_map::PAN_CAMERA => self.panning = pressed,
+            _ => return false,
+        }
+        true
+    }
+
+    fn process_axis(&mut self, axis: &str, value: f32) -> bool {
+        match axis {
+            input_map::ZOOM => self.scroll += value,
+            _ => return false,
+        }
+        true
+    }
+
+    fn dragging(&self) -> bool {
+        self.rotating || self.panning
+    }
+
+    fn release_all(&mut self) {
+        self.rotating = false;
+        self.panning = false;
+    }
+
+    fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        let motion = Vector2::new(mouse_dx as f32, mouse_dy as f32);
+        if self.panning {
+            self.pan += motion;
+        } else if self.rotating {
+            self.rotate += motion;
+        }
+    }
+
+    // takes the accumulated rotate, pan and scroll amounts
+    fn take(&mut self) -> (Vector2<f32>, Vector2<f32>, f32) {
+        let taken = (self.rotate, self.pan, self.scroll);
+        self.rotate = Vector2::zero();
+        self.pan = Vector2::zero();
+        self.scroll = 0.0;
+        taken
+    }
+}
+
+/// Center and camera distance that fit the box `min..max` in view.
+///
+/// With a perspective projection the bounding sphere fits the narrower field of view.
+/// Orthographic projections don't change size with distance, the camera only backs off far
+/// enough to have the box in front of it and the scaling is left alone.
+pub fn fit_box(min: Point3<f32>, max: Point3<f32>, projection: &Projection) -> (Point3<f32>, f32) {
+    let center = min.midpoint(max);
+    let radius = (max - min).magnitude() * 0.5;
+
+    let distance = match projection.kind() {
+        ProjectionKind::Perspective { fovy } => {
+            let half_fovy = fovy.0 * 0.5;
+            let half_fovx = (half_fovy.tan() * projection.aspect()).atan();
+            radius / half_fovy.min(half_fovx).sin()
+        }
+        ProjectionKind::Orthographic { .. } => radius * 2.0,
+    };
+
+    (center, distance.max(projection.znear() + radius))
+}
+
+fn zoom(distance: f32, scroll: f32, zoom_speed: f32, min: f32, max: f32) -> f32 {
+    (distance * (1.0 - zoom_speed).powf(scroll)).clamp(min, max)
+}
+
+/// Turntable camera circling `target`, yaw around +Y and pitch limited short of the poles.
+///
+/// Dragging with `ROTATE_CAMERA` held rotates, with `PAN_CAMERA` held pans, `ZOOM` dollies in and out.
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub sensitivity: f32,
+    /// fraction of the distance panned per pixel dragged
+    pub pan_speed: f32,
+    /// fraction of the distance covered per scroll line
+    pub zoom_speed: f32,
+    yaw: Rad<f32>,
+    pitch: Rad<f32>,
+    drag: Drag,
+}
+
+impl OrbitController {
+    /// looking along +Z at `target` from slightly above
+    pub fn new<P: Into<Point3<f32>>>(target: P, distance: f32) -> Self {
+        Self {
+            target: target.into(),
+            distance,
+            min_distance: 0.05,
+            max_distance: 1000.0,
+            sensitivity: 1.0,
+            pan_speed: 0.0015,
+            zoom_speed: 0.1,
+            yaw: Rad(std::f32::consts::FRAC_PI_2),
+            pitch: Rad(-0.3),
+            drag: Default::default(),
+        }
+    }
+
+    /// takes over from wherever `camera` is, circling `target`
+    pub fn from_camera<P: Into<Point3<f32>>>(camera: &Camera, target: P) -> Self {
+        let target = target.into();
+        let offset = target - camera.position;
+        let mut controller = Self::new(target, offset.magnitude().max(0.05));
+        let forward = offset.normalize();
+        controller.yaw = Rad(forward.z.atan2(forward.x));
+        controller.pitch = Rad(forward.y.clamp(-1.0, 1.0).asin());
+        controller
+    }
+
+    /// moves the target to the center of `min..max` and backs off until it's all in view
+    pub fn zoom_to_fit(&mut self, min: Point3<f32>, max: Point3<f32>, projection: &Projection) {
+        (self.target, self.distance) = fit_box(min, max, projection);
+        self.max_distance = self.max_distance.max(self.distance);
+    }
+
+    pub fn process_action(&mut self, action: &str, pressed: bool) -> bool {
+        self.drag.process_action(action, pressed)
+    }
+
+    pub fn process_axis(&mut self, axis: &str, value: f32) -> bool {
+        self.drag.process_axis(axis, value)
+    }
+
+    pub fn dragging(&self) -> bool {
+        self.drag.dragging()
+    }
+
+    pub fn release_all(&mut self) {
+        self.drag.release_all();
+    }
+
+    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        self.drag.process_mouse(mouse_dx, mouse_dy);
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera, _dt: instant::Duration) {
+        let (rotate, pan, scroll) = self.drag.take();
+
+        let angle = RADIANS_PER_PIXEL * self.sensitivity;
+        self.yaw += Rad(rotate.x * angle);
+        self.pitch = Rad((self.pitch.0 - rotate.y * angle).clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2));
+
+        let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
+        let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+        let forward = Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw);
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+
+        self.target += (up * pan.y - right * pan.x) * self.distance * self.pan_speed;
+        self.distance = zoom(
+            self.distance,
+            scroll,
+            self.zoom_speed,
+            self.min_distance,
+            self.max_distance,
+        );
+
+        camera.position = self.target - forward * self.distance;
+        camera.set_orientation(forward, Vector3::unit_y());
+    }
+}
+
+/// Free rotation around `target` with no fixed up direction, so the model can be turned any way
+/// including upside down.
+///
+/// The mouse only reports relative motion, so each drag turns around the axis perpendicular to
+/// the drag on screen (a virtual trackball) rather than projecting the cursor onto a sphere.
+/// Panning and scrolling work like `OrbitController`.
+pub struct ArcballController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub sensitivity: f32,
+    pub pan_speed: f32,
+    pub zoom_speed: f32,
+    // camera to world rotation, the camera looks down its -Z
+    rotation: Quaternion<f32>,
+    drag: Drag,
+}
+
+impl ArcballController {
+    /// looking along -Z at `target`
+    pub fn new<P: Into<Point3<f32>>>(target: P, distance: f32) -> Self {
+        Self {
+            target: target.into(),
+            distance,
+            min_distance: 0.05,
+            max_distance: 1000.0,
+            sensitivity: 1.0,
+            pan_speed: 0.0015,
+            zoom_speed: 0.1,
+            rotation: Quaternion::one(),
+            drag: Default::default(),
+        }
+    }
+
+    /// takes over from wherever `camera` is, rotating around `target`
+    pub fn from_camera<P: Into<Point3<f32>>>(camera: &Camera, target: P) -> Self {
+        let target = target.into();
+        let offset = target - camera.position;
+        let mut controller = Self::new(target, offset.magnitude().max(0.05));
+
+        let forward = offset.normalize();
+        let mut right = forward.cross(camera.up());
+        if right.magnitude2() < 1e-6 {
+            right = forward.cross(Vector3::unit_z());
+        }
+        let right = right.normalize();
+        let up = right.cross(forward);
+        controller.rotation = Quaternion::from(Matrix3::from_cols(right, up, -forward));
+        controller
+    }
+
+    pub fn zoom_to_fit(&mut self, min: Point3<f32>, max: Point3<f32>, projection: &Projection) {
+        (self.target, self.distance) = fit_box(min, max, projection);
+        self.max_distance = self.max_distance.max(self.distance);
+    }
+
+    pub fn process_action(&mut self, action: &str, pressed: bool) -> bool {
+        self.drag.process_action(action, pressed)
+    }
+
+    pub fn process_axis(&mut self, axis: &str, value: f32) -> bool {
+        self.drag.process_axis(axis, value)
+    }
+
+    pub fn dragging(&self) -> bool {
+        self.drag.dragging()
+    }
+
+    pub fn release_all(&mut self) {
+        self.drag.release_all();
+    }
+
+    pub fn process_mouse(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        self.drag.process_mouse(mouse_dx, mouse_dy);
+    }
+
+    pub fn update_camera(&mut self, camera: &mut Camera, _dt: instant::Duration) {
+        let (rotate, pan, scroll) = self.drag.take();
+
+        // dragging right turns the model right, so the camera swings left around it
+        let pixels = rotate.magnitude();
+        if pixels > 0.0 {
+            let axis = Vector3::new(-rotate.y, -rotate.x, 0.0) / pixels;
+            let angle = Rad(pixels * RADIANS_PER_PIXEL * self.sensitivity);
+            self.rotation = (self.rotation * Quaternion::from_axis_angle(axis, angle)).normalize();
+        }
+
+        let forward = self.rotation * -Vector3::unit_z();
+        let right = self.rotation * Vector3::unit_x();
+        let up = self.rotation * Vector3::unit_y();
+
+        self.target += (up * pan.y - right * pan.x) * self.distance * self.pan_speed;
+        self.distance = zoom(
+            self.distance,
+            scroll,
+            self.zoom_speed,
+            self.min_distance,
+            self.max_distance,
+        );
+
+        camera.position = self.target - forward * self.distance;
+        camera.set_orientation(forward, up);
+    }
+}
@@ -5,14 +5,19 @@ pub struct PipelineObject {
     pub polygon_mode: wgpu::PolygonMode,
 }
 
+/// creates the pipelines for a given MSAA sample count
+pub type PipelineBuilder = Box<dyn Fn(&wgpu::Device, u32) -> Vec<PipelineObject>>;
+
 pub struct PipelineManager {
     pub pipelines: Vec<PipelineObject>,
+    builder: Option<PipelineBuilder>,
 }
 
 impl Default for PipelineManager {
     fn default() -> Self {
         Self {
             pipelines: Default::default(),
+            builder: None,
         }
     }
 }
@@ -28,6 +33,24 @@ impl PipelineManager {
         }
     }
 
+    /// Builds the pipelines with `builder` and keeps it, so they can be rebuilt when the sample count changes.
+    /// Replaces any pipelines added before
+    pub fn set_builder(&mut self, device: &wgpu::Device, sample_count: u32, builder: PipelineBuilder) {
+        self.pipelines = builder(device, sample_count);
+        self.builder = Some(builder);
+    }
+
+    pub fn has_builder(&self) -> bool {
+        self.builder.is_some()
+    }
+
+    /// rebuilds the pipelines for a new sample count, does nothing if no builder was set
+    pub fn rebuild(&mut self, device: &wgpu::Device, sample_count: u32) {
+        if let Some(builder) = &self.builder {
+            self.pipelines = builder(device, sample_count);
+        }
+    }
+
     pub fn first_polygon_fill_item(&self) -> &wgpu::RenderPipeline {
         for pipeline in &self.pipelines {
             if pipeline.polygon_mode == wgpu::PolygonMode::Fill {
@@ -49,6 +72,14 @@ impl PipelineManager {
     }
 }
 
+pub fn create_multisample_state(sample_count: u32) -> wgpu::MultisampleState {
+    wgpu::MultisampleState {
+        count: sample_count,
+        mask: !0,
+        alpha_to_coverage_enabled: false,
+    }
+}
+
 pub fn create_depth_stencil_state() -> Option<wgpu::DepthStencilState> {
     Some(wgpu::DepthStencilState{
         format: texture::Texture::DEPTH_FORMAT,
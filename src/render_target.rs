@@ -0,0 +1,349 @@
+use anyhow::*;
+
+use crate::texture::Texture;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TargetSize {
+    Fixed {
+        width: u32,
+        height: u32,
+    },
+    /// follows the window, `scale` times its size, and is recreated when the window resizes
+    Window {
+        scale: f32,
+    },
+}
+
+impl TargetSize {
+    /// size in pixels for a window of `window_width` x `window_height`, never smaller than 1x1
+    pub fn resolve(&self, window_width: u32, window_height: u32) -> (u32, u32) {
+        match *self {
+            TargetSize::Fixed { width, height } => (width.max(1), height.max(1)),
+            TargetSize::Window { scale } => (
+                ((window_width as f32 * scale).round() as u32).max(1),
+                ((window_height as f32 * scale).round() as u32).max(1),
+            ),
+        }
+    }
+}
+
+pub struct RenderTargetDesc<'a> {
+    pub label: Option<&'a str>,
+    pub size: TargetSize,
+    /// one color texture per format, the first one has to be filterable since it's what `bind_group` samples
+    pub color_formats: &'a [wgpu::TextureFormat],
+    pub depth: bool,
+    /// has to match the pipelines drawing into it, i.e. `Renderer::sample_count()`. The engine keeps
+    /// the targets in `Renderer::render_target_manager` in step when MSAA changes
+    pub sample_count: u32,
+}
+
+/// Offscreen color (and optionally depth) textures that a camera renders into and
+/// materials sample afterwards, e.g. for mirrors, in-game screens or a minimap.
+///
+/// `bind_group` has the usual material layout (texture at 0, filtering sampler at 1) over the
+/// first color texture, so it can be used in place of a material bind group. It's recreated on
+/// `resize`, so look it up every frame rather than holding on to it.
+///
+/// With MSAA the passes render into `msaa_colors` and resolve into `colors`, the depth texture is
+/// multisampled too.
+pub struct RenderTarget {
+    pub colors: Vec<Texture>,
+    /// one multisampled texture per color, empty without MSAA
+    pub msaa_colors: Vec<Texture>,
+    pub depth: Option<Texture>,
+    pub size: TargetSize,
+    pub width: u32,
+    pub height: u32,
+    pub sample_count: u32,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+    label: Option<String>,
+    color_formats: Vec<wgpu::TextureFormat>,
+}
+
+impl RenderTarget {
+    pub fn new(
+        device: &wgpu::Device,
+        desc: &RenderTargetDesc,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> Result<Self> {
+        let Some(&first_format) = desc.color_formats.first() else {
+            bail!("render target needs at least one color format");
+        };
+        if !first_format
+            .guaranteed_format_features(device.features())
+            .flags
+            .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE)
+        {
+            bail!("{:?} can't be sampled with filtering", first_format);
+        }
+
+        let (width, height) = desc.size.resolve(config.width, config.height);
+        let label = desc.label.map(str::to_string);
+        let colors = Self::create_colors(device, desc.color_formats, width, height, desc.label);
+        let msaa_colors = Self::create_msaa_colors(
+            device,
+            desc.color_formats,
+            width,
+            height,
+            desc.sample_count,
+            desc.label,
+        );
+        let depth = desc.depth.then(|| {
+            Texture::create_sized_depth_texture(
+                device,
+                width,
+                height,
+                desc.sample_count,
+                desc.label,
+            )
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Render Target Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &colors[0]);
+
+        Ok(Self {
+            colors,
+            msaa_colors,
+            depth,
+            size: desc.size,
+            width,
+            height,
+            sample_count: desc.sample_count,
+            bind_group_layout,
+            bind_group,
+            label,
+            color_formats: desc.color_formats.to_vec(),
+        })
+    }
+
+    fn create_colors(
+        device: &wgpu::Device,
+        formats: &[wgpu::TextureFormat],
+        width: u32,
+        height: u32,
+        label: Option<&str>,
+    ) -> Vec<Texture> {
+        formats
+            .iter()
+            .map(|&format| Texture::create_color_target(device, width, height, format, label))
+            .collect()
+    }
+
+    fn create_msaa_colors(
+        device: &wgpu::Device,
+        formats: &[wgpu::TextureFormat],
+        width: u32,
+        height: u32,
+        sample_count: u32,
+        label: Option<&str>,
+    ) -> Vec<Texture> {
+        if sample_count == 1 {
+            return vec![];
+        }
+        formats
+            .iter()
+            .map(|&format| {
+                Texture::create_msaa_target(device, width, height, format, sample_count, label)
+            })
+            .collect()
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        color: &Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Render Target Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&color.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&color.sampler),
+                },
+            ],
+        })
+    }
+
+    /// recreates the textures for a new window size, fixed size targets are left alone
+    pub fn resize(&mut self, device: &wgpu::Device, window_width: u32, window_height: u32) {
+        if let TargetSize::Fixed { .. } = self.size {
+            return;
+        }
+
+        let (width, height) = self.size.resolve(window_width, window_height);
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.create_textures(device);
+    }
+
+    /// recreates the multisampled and depth textures for pipelines with another sample count
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        if sample_count == self.sample_count {
+            return;
+        }
+
+        self.sample_count = sample_count;
+        self.create_textures(device);
+    }
+
+    fn create_textures(&mut self, device: &wgpu::Device) {
+        let (width, height, sample_count) = (self.width, self.height, self.sample_count);
+        let label = self.label.as_deref();
+        self.colors = Self::create_colors(device, &self.color_formats, width, height, label);
+        self.msaa_colors = Self::create_msaa_colors(
+            device,
+            &self.color_formats,
+            width,
+            height,
+            sample_count,
+            label,
+        );
+        if self.depth.is_some() {
+            self.depth = Some(Texture::create_sized_depth_texture(
+                device,
+                width,
+                height,
+                sample_count,
+                label,
+            ));
+        }
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.colors[0]);
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+
+    pub fn formats(&self) -> &[wgpu::TextureFormat] {
+        &self.color_formats
+    }
+
+    /// one attachment per color texture, cleared to `clear_color`
+    pub fn color_attachments(
+        &self,
+        clear_color: wgpu::Color,
+    ) -> Vec<Option<wgpu::RenderPassColorAttachment<'_>>> {
+        self.color_attachments_with(wgpu::LoadOp::Clear(clear_color))
+    }
+
+    /// One attachment per color texture starting with `load`. With MSAA they render into the
+    /// multisampled textures and resolve to the sampled ones, which are kept for later passes
+    /// that load them
+    pub fn color_attachments_with(
+        &self,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> Vec<Option<wgpu::RenderPassColorAttachment<'_>>> {
+        let ops = wgpu::Operations {
+            load,
+            store: wgpu::StoreOp::Store,
+        };
+        if self.msaa_colors.is_empty() {
+            return self
+                .colors
+                .iter()
+                .map(|color| {
+                    Some(wgpu::RenderPassColorAttachment {
+                        view: &color.view,
+                        resolve_target: None,
+                        ops,
+                    })
+                })
+                .collect();
+        }
+
+        self.msaa_colors
+            .iter()
+            .zip(&self.colors)
+            .map(|(msaa, color)| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view: &msaa.view,
+                    resolve_target: Some(&color.view),
+                    ops,
+                })
+            })
+            .collect()
+    }
+
+    pub fn depth_attachment(&self) -> Option<wgpu::RenderPassDepthStencilAttachment<'_>> {
+        self.depth
+            .as_ref()
+            .map(|depth| wgpu::RenderPassDepthStencilAttachment {
+                view: &depth.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            })
+    }
+}
+
+pub struct RenderTargetItem {
+    pub id: u32,
+    pub render_target: RenderTarget,
+}
+
+/// Render targets by id, window sized ones are resized along with the window by the engine
+#[derive(Default)]
+pub struct RenderTargetManager {
+    pub render_target_list: Vec<RenderTargetItem>,
+}
+
+impl RenderTargetManager {
+    pub fn add_render_target(&mut self, id: u32, render_target: RenderTarget) {
+        self.render_target_list
+            .push(RenderTargetItem { id, render_target })
+    }
+
+    pub fn find_by_id(&self, id: u32) -> Option<&RenderTargetItem> {
+        self.render_target_list.iter().find(|&x| x.id == id)
+    }
+
+    pub fn find_by_id_mut(&mut self, id: u32) -> Option<&mut RenderTargetItem> {
+        self.render_target_list.iter_mut().find(|x| x.id == id)
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, window_width: u32, window_height: u32) {
+        for item in &mut self.render_target_list {
+            item.render_target
+                .resize(device, window_width, window_height);
+        }
+    }
+
+    pub fn set_sample_count(&mut self, device: &wgpu::Device, sample_count: u32) {
+        for item in &mut self.render_target_list {
+            item.render_target.set_sample_count(device, sample_count);
+        }
+    }
+}
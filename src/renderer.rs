@@ -1,4 +1,14 @@
+use crate::antialias::{AntiAliasPass, AntiAliasing};
+use crate::bloom::{BloomPass, BloomSettings};
+use crate::culling::CullStats;
+use crate::gpu_culling::GpuCullingPass;
+use crate::hdr::{Exposure, HdrPass, Tonemapper, HDR_FORMAT};
+use crate::ibl::EnvironmentMaps;
 use crate::pipeline::*;
+use crate::render_target::RenderTargetManager;
+use crate::sampler::SamplerManager;
+use crate::shadow::ShadowPass;
+use crate::skybox::SkyboxPass;
 use crate::texture::*;
 
 pub struct Renderer {
@@ -8,8 +18,25 @@ pub struct Renderer {
     pub index_buffer: Option<wgpu::Buffer>,
     pub texture: Option<Texture>,
     pub depth_texture: Option<Texture>,
+    /// multisampled scene color target, only there while `sample_count() > 1`
+    pub msaa_texture: Option<Texture>,
+    pub shadow_pass: Option<ShadowPass>,
+    pub hdr_pass: Option<HdrPass>,
+    pub bloom_pass: Option<BloomPass>,
+    pub antialias_pass: Option<AntiAliasPass>,
+    pub skybox_pass: Option<SkyboxPass>,
+    /// culling and indirect draws for large instanced scenes, kept in step with the depth texture
+    pub gpu_culling_pass: Option<GpuCullingPass>,
+    /// image based lighting for lit shaders, see `ibl::shader_snippet`
+    pub environment_maps: Option<EnvironmentMaps>,
     pub bind_group_manager: BindGroupManager,
     pub buffer_manager: BufferManager,
+    pub sampler_manager: SamplerManager,
+    pub render_target_manager: RenderTargetManager,
+    /// objects the frustum culling kept and dropped, see `Frustum::visible_objects`
+    pub cull_stats: CullStats,
+    // sample count the targets and pipelines currently use, `RenderState::sample_count` is the requested one
+    pub(crate) sample_count: u32,
 }
 
 impl Default for Renderer {
@@ -21,15 +48,79 @@ impl Default for Renderer {
             index_buffer: None,
             texture: None,
             depth_texture: None,
+            msaa_texture: None,
+            shadow_pass: None,
+            hdr_pass: None,
+            bloom_pass: None,
+            antialias_pass: None,
+            skybox_pass: None,
+            gpu_culling_pass: None,
+            environment_maps: None,
             bind_group_manager: Default::default(),
             buffer_manager: Default::default(),
+            sampler_manager: Default::default(),
+            render_target_manager: Default::default(),
+            cull_stats: Default::default(),
+            sample_count: 1,
+        }
+    }
+}
+
+impl Renderer {
+    /// sample count the scene pipelines have to be created with
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// format of the scene color target, the HDR target if there is one, otherwise the swapchain
+    pub fn scene_format(&self, config: &wgpu::SurfaceConfiguration) -> wgpu::TextureFormat {
+        match self.hdr_pass {
+            Some(_) => HDR_FORMAT,
+            None => config.format,
+        }
+    }
+
+    /// Color attachment for the scene pass that clears to `clear_color`.
+    /// With MSAA on it renders into `msaa_texture` and resolves to `target`
+    pub fn color_attachment<'a>(
+        &'a self,
+        target: &'a wgpu::TextureView,
+    ) -> wgpu::RenderPassColorAttachment<'a> {
+        let load = wgpu::LoadOp::Clear(self.state.clear_color);
+        match &self.msaa_texture {
+            Some(msaa) => wgpu::RenderPassColorAttachment {
+                view: &msaa.view,
+                resolve_target: Some(target),
+                ops: wgpu::Operations {
+                    load,
+                    // only the resolved result is needed
+                    store: wgpu::StoreOp::Discard,
+                },
+            },
+            None => wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            },
         }
     }
 }
 
 pub struct RenderState {
+    /// hidden behind the sky while a skybox pass draws it
     pub clear_color: wgpu::Color,
     pub polygon_fill: bool,
+    pub tonemapper: Tonemapper,
+    pub exposure: Exposure,
+    pub bloom: BloomSettings,
+    /// MSAA sample count, checked against what the adapter supports before it's applied
+    pub sample_count: u32,
+    pub anti_aliasing: AntiAliasing,
+    /// draw the sky instead of leaving the clear color, when there is a skybox pass
+    pub skybox: bool,
 }
 
 impl Default for RenderState {
@@ -37,6 +128,12 @@ impl Default for RenderState {
         Self {
             polygon_fill: true,
             clear_color: wgpu::Color::BLACK,
+            tonemapper: Tonemapper::Aces,
+            exposure: Exposure::auto(),
+            bloom: Default::default(),
+            sample_count: 1,
+            anti_aliasing: AntiAliasing::None,
+            skybox: true,
         }
     }
 }
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use crate::texture::MipmapGenerator;
+
+/// Everything that goes into a sampler, comparable so equal samplers can be shared.
+///
+/// The default matches the sampler textures always had: clamped, linear magnification,
+/// nearest minification and mip selection.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerDesc {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub address_mode_w: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    pub lod_min_clamp: f32,
+    pub lod_max_clamp: f32,
+    /// Maximum anisotropy, 1 turns it off. Only applies when all three filters are `Linear`,
+    /// otherwise it's ignored since wgpu rejects the combination
+    pub anisotropy_clamp: u16,
+}
+
+impl Default for SamplerDesc {
+    fn default() -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 32.0,
+            anisotropy_clamp: 1,
+        }
+    }
+}
+
+impl SamplerDesc {
+    /// trilinear filtering that repeats in every direction, for tiling textures with mipmaps
+    pub fn repeat(anisotropy_clamp: u16) -> Self {
+        Self {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp,
+            ..Default::default()
+        }
+    }
+
+    pub fn descriptor<'a>(&self, label: Option<&'a str>) -> wgpu::SamplerDescriptor<'a> {
+        let all_linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|filter| *filter == wgpu::FilterMode::Linear);
+
+        wgpu::SamplerDescriptor {
+            label,
+            address_mode_u: self.address_mode_u,
+            address_mode_v: self.address_mode_v,
+            address_mode_w: self.address_mode_w,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            lod_min_clamp: self.lod_min_clamp,
+            lod_max_clamp: self.lod_max_clamp,
+            compare: None,
+            anisotropy_clamp: if all_linear {
+                self.anisotropy_clamp.max(1)
+            } else {
+                1
+            },
+            border_color: None,
+        }
+    }
+
+    pub fn create(&self, device: &wgpu::Device) -> wgpu::Sampler {
+        device.create_sampler(&self.descriptor(None))
+    }
+}
+
+pub struct SamplerItem {
+    pub desc: SamplerDesc,
+    pub sampler: Arc<wgpu::Sampler>,
+}
+
+/// Hands out one shared sampler per distinct `SamplerDesc`
+#[derive(Default)]
+pub struct SamplerManager {
+    pub sampler_list: Vec<SamplerItem>,
+    /// mip chain pipelines, built once per format and reused by every texture created with mipmaps
+    pub mipmaps: MipmapGenerator,
+}
+
+impl SamplerManager {
+    /// the sampler for `desc`, created on first use
+    pub fn get(&mut self, device: &wgpu::Device, desc: &SamplerDesc) -> Arc<wgpu::Sampler> {
+        if let Some(item) = self.sampler_list.iter().find(|x| x.desc == *desc) {
+            return item.sampler.clone();
+        }
+
+        let sampler = Arc::new(desc.create(device));
+        self.sampler_list.push(SamplerItem {
+            desc: *desc,
+            sampler: sampler.clone(),
+        });
+        sampler
+    }
+}
@@ -1,13 +1,30 @@
 use crate::camera::{Camera, CameraController, CameraUniform, Projection};
+use crate::camera_path::CameraPathPlayer;
+use crate::light::Light;
 use crate::object::*;
 use crate::pipeline::PipelineObject;
+use crate::viewport::{CameraView, ViewSettings};
+
+/// The main camera or one of `Scene::cameras` by index
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SceneView {
+    Main,
+    Camera(usize),
+}
 
 pub struct Scene {
     pub camera: Camera,
     pub camera_controller: CameraController,
     pub camera_uniform: CameraUniform,
     pub projection: Projection,
+    /// where and when the main camera renders, the whole window at order 0 by default
+    pub main_view: ViewSettings,
+    /// cameras rendered besides the main one, see `CameraView`
+    pub cameras: Vec<CameraView>,
+    /// recorded path the main camera can play back instead of following the controller
+    pub camera_path: CameraPathPlayer,
     pub objects: Vec<Object>,
+    pub lights: Vec<Light>,
     pub pipeline_objects: Vec<PipelineObject>,
 }
 
@@ -18,7 +35,11 @@ impl Default for Scene {
             camera_controller: CameraController::new(5.0, 1.0),
             camera_uniform: CameraUniform::new(),
             projection: Projection::new(1, 1, cgmath::Deg(45.0), 0.1, 100.0),
+            main_view: Default::default(),
+            cameras: vec![],
+            camera_path: Default::default(),
             objects: vec![],
+            lights: vec![],
             pipeline_objects: vec![],
         }
     }
@@ -28,4 +49,25 @@ impl Scene {
     pub fn update_pipelines(&mut self, pipelines: &mut Vec<PipelineObject>) {
         self.pipeline_objects.append(pipelines);
     }
+
+    /// every view in render order, see `ViewSettings`
+    pub fn ordered_views(&self) -> Vec<SceneView> {
+        let mut views = vec![SceneView::Main];
+        views.extend((0..self.cameras.len()).map(SceneView::Camera));
+        views.sort_by_key(|&view| self.view_settings(view).order);
+        views
+    }
+
+    pub fn view_settings(&self, view: SceneView) -> &ViewSettings {
+        match view {
+            SceneView::Main => &self.main_view,
+            SceneView::Camera(i) => &self.cameras[i].settings,
+        }
+    }
+
+    pub fn update_cameras(&mut self) {
+        for view in &mut self.cameras {
+            view.update_uniform();
+        }
+    }
 }
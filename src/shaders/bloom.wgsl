@@ -0,0 +1,120 @@
+// appended to fullscreen.wgsl
+// downsample / upsample filters from "Next Generation Post Processing in Call of Duty: Advanced Warfare"
+
+// threshold: brightness where bloom starts (0 disables it), knee: softness of the threshold curve,
+// radius: upsample filter radius in texels
+struct BloomParams {
+    threshold: f32,
+    knee: f32,
+    radius: f32,
+    _padding: f32,
+}
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+@group(0) @binding(2)
+var<uniform> params: BloomParams;
+
+fn luminance(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// weights a group of samples by 1 / (1 + luma) so single very bright pixels don't flicker
+fn karis_average(a: vec3<f32>, b: vec3<f32>, c: vec3<f32>, d: vec3<f32>) -> vec3<f32> {
+    let wa = 1.0 / (1.0 + luminance(a));
+    let wb = 1.0 / (1.0 + luminance(b));
+    let wc = 1.0 / (1.0 + luminance(c));
+    let wd = 1.0 / (1.0 + luminance(d));
+    return (a * wa + b * wb + c * wc + d * wd) / (wa + wb + wc + wd);
+}
+
+// quadratic soft threshold
+fn soft_threshold(color: vec3<f32>) -> vec3<f32> {
+    if (params.threshold <= 0.0) {
+        return color;
+    }
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = params.threshold * params.knee + 0.00001;
+    var soft = clamp(brightness - params.threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    let contribution = max(soft, brightness - params.threshold) / max(brightness, 0.00001);
+    return color * contribution;
+}
+
+fn sample_source(uv: vec2<f32>, offset: vec2<f32>) -> vec3<f32> {
+    return textureSample(source_texture, source_sampler, uv + offset).rgb;
+}
+
+fn downsample_13(uv: vec2<f32>, first: bool) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source_texture));
+    let x = texel.x;
+    let y = texel.y;
+
+    // a - b - c
+    // - j - k -
+    // d - e - f
+    // - l - m -
+    // g - h - i
+    let a = sample_source(uv, vec2<f32>(-2.0 * x, 2.0 * y));
+    let b = sample_source(uv, vec2<f32>(0.0, 2.0 * y));
+    let c = sample_source(uv, vec2<f32>(2.0 * x, 2.0 * y));
+    let d = sample_source(uv, vec2<f32>(-2.0 * x, 0.0));
+    let e = sample_source(uv, vec2<f32>(0.0, 0.0));
+    let f = sample_source(uv, vec2<f32>(2.0 * x, 0.0));
+    let g = sample_source(uv, vec2<f32>(-2.0 * x, -2.0 * y));
+    let h = sample_source(uv, vec2<f32>(0.0, -2.0 * y));
+    let i = sample_source(uv, vec2<f32>(2.0 * x, -2.0 * y));
+    let j = sample_source(uv, vec2<f32>(-x, y));
+    let k = sample_source(uv, vec2<f32>(x, y));
+    let l = sample_source(uv, vec2<f32>(-x, -y));
+    let m = sample_source(uv, vec2<f32>(x, -y));
+
+    if (first) {
+        let center = karis_average(j, k, l, m) * 0.5;
+        let corners = (karis_average(a, b, d, e) + karis_average(b, c, e, f)
+            + karis_average(d, e, g, h) + karis_average(e, f, h, i)) * 0.125;
+        return center + corners;
+    }
+
+    return e * 0.125
+        + (a + c + g + i) * 0.03125
+        + (b + d + f + h) * 0.0625
+        + (j + k + l + m) * 0.125;
+}
+
+@fragment
+fn fs_downsample_first(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = soft_threshold(downsample_13(in.uv, true));
+    return vec4<f32>(max(color, vec3<f32>(0.0)), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample_13(in.uv, false), 1.0);
+}
+
+// 3x3 tent filter, blended additively into the next larger mip
+@fragment
+fn fs_upsample(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let texel = params.radius / vec2<f32>(textureDimensions(source_texture));
+    let x = texel.x;
+    let y = texel.y;
+
+    var color = sample_source(in.uv, vec2<f32>(0.0, 0.0)) * 4.0;
+    color += (sample_source(in.uv, vec2<f32>(0.0, y)) + sample_source(in.uv, vec2<f32>(-x, 0.0))
+        + sample_source(in.uv, vec2<f32>(x, 0.0)) + sample_source(in.uv, vec2<f32>(0.0, -y))) * 2.0;
+    color += sample_source(in.uv, vec2<f32>(-x, y)) + sample_source(in.uv, vec2<f32>(x, y))
+        + sample_source(in.uv, vec2<f32>(-x, -y)) + sample_source(in.uv, vec2<f32>(x, -y));
+
+    return vec4<f32>(color / 16.0, 1.0);
+}
+
+// the blend state does the mixing with the scene, see `BloomComposite`
+@fragment
+fn fs_composite(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureSample(source_texture, source_sampler, in.uv).rgb, 1.0);
+}
@@ -0,0 +1,57 @@
+// writes the 6 faces of a cube map from an equirectangular (lat-long) image
+
+@group(0) @binding(0)
+var equirect: texture_2d<f32>;
+
+@group(0) @binding(1)
+var cube_faces: texture_storage_2d_array<rgba16float, write>;
+
+const PI: f32 = 3.14159265359;
+
+// direction through texel `uv` (-1..1, v pointing down) of cube face `face`, in +X, -X, +Y, -Y, +Z, -Z order
+fn cube_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    switch face {
+        case 0u: { return normalize(vec3<f32>(1.0, -uv.y, -uv.x)); }
+        case 1u: { return normalize(vec3<f32>(-1.0, -uv.y, uv.x)); }
+        case 2u: { return normalize(vec3<f32>(uv.x, 1.0, uv.y)); }
+        case 3u: { return normalize(vec3<f32>(uv.x, -1.0, -uv.y)); }
+        case 4u: { return normalize(vec3<f32>(uv.x, -uv.y, 1.0)); }
+        default: { return normalize(vec3<f32>(-uv.x, -uv.y, -1.0)); }
+    }
+}
+
+// float textures aren't filterable everywhere, filter by hand
+fn load_bilinear(uv: vec2<f32>) -> vec4<f32> {
+    let size = vec2<i32>(textureDimensions(equirect));
+    let position = uv * vec2<f32>(size) - 0.5;
+    let base = vec2<i32>(floor(position));
+    let t = fract(position);
+
+    // wrap horizontally around the sphere, clamp at the poles
+    let x0 = (base.x % size.x + size.x) % size.x;
+    let x1 = (x0 + 1) % size.x;
+    let y0 = clamp(base.y, 0, size.y - 1);
+    let y1 = clamp(base.y + 1, 0, size.y - 1);
+
+    let top = mix(textureLoad(equirect, vec2<i32>(x0, y0), 0), textureLoad(equirect, vec2<i32>(x1, y0), 0), t.x);
+    let bottom = mix(textureLoad(equirect, vec2<i32>(x0, y1), 0), textureLoad(equirect, vec2<i32>(x1, y1), 0), t.x);
+    return mix(top, bottom, t.y);
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(cube_faces).xy;
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    let uv = (vec2<f32>(id.xy) + 0.5) / vec2<f32>(size) * 2.0 - 1.0;
+    let direction = cube_direction(id.z, uv);
+
+    let longitude = atan2(direction.z, direction.x);
+    let latitude = asin(clamp(direction.y, -1.0, 1.0));
+    let equirect_uv = vec2<f32>(longitude / (2.0 * PI) + 0.5, 0.5 - latitude / PI);
+
+    textureStore(cube_faces, id.xy, id.z, vec4<f32>(load_bilinear(equirect_uv).rgb, 1.0));
+}
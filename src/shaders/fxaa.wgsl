@@ -0,0 +1,56 @@
+// appended to fullscreen.wgsl
+// FXAA, based on Timothy Lottes' FXAA 3.11 console version
+
+const FXAA_REDUCE_MIN: f32 = 0.0078125; // 1 / 128
+const FXAA_REDUCE_MUL: f32 = 0.125;
+const FXAA_SPAN_MAX: f32 = 8.0;
+
+@group(0) @binding(0)
+var source_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+// edges are found on the HDR target, compress it first so bright areas don't dominate
+fn fxaa_luma(color: vec3<f32>) -> f32 {
+    let compressed = color / (vec3<f32>(1.0) + color);
+    return dot(compressed, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn sample_color(uv: vec2<f32>) -> vec3<f32> {
+    return textureSample(source_texture, source_sampler, uv).rgb;
+}
+
+@fragment
+fn fs_fxaa(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source_texture));
+    let uv = in.uv;
+
+    let color_m = sample_color(uv);
+    let luma_nw = fxaa_luma(sample_color(uv + vec2<f32>(-1.0, -1.0) * texel));
+    let luma_ne = fxaa_luma(sample_color(uv + vec2<f32>(1.0, -1.0) * texel));
+    let luma_sw = fxaa_luma(sample_color(uv + vec2<f32>(-1.0, 1.0) * texel));
+    let luma_se = fxaa_luma(sample_color(uv + vec2<f32>(1.0, 1.0) * texel));
+    let luma_m = fxaa_luma(color_m);
+
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    // blur direction is along the edge, perpendicular to the luma gradient
+    var dir = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let dir_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    let rcp_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+    dir = clamp(dir * rcp_dir_min, vec2<f32>(-FXAA_SPAN_MAX), vec2<f32>(FXAA_SPAN_MAX)) * texel;
+
+    let color_a = 0.5 * (sample_color(uv + dir * (1.0 / 3.0 - 0.5)) + sample_color(uv + dir * (2.0 / 3.0 - 0.5)));
+    let color_b = color_a * 0.5 + 0.25 * (sample_color(uv + dir * -0.5) + sample_color(uv + dir * 0.5));
+
+    // the wider tap went past the edge, fall back to the narrow one
+    let luma_b = fxaa_luma(color_b);
+    let color = select(color_b, color_a, luma_b < luma_min || luma_b > luma_max);
+
+    return vec4<f32>(color, 1.0);
+}
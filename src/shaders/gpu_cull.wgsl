@@ -0,0 +1,117 @@
+// tests every instance against the frustum (and the Hi-Z pyramid), visible ones are appended to
+// their batch's range of `visible` and counted in its indirect draw
+
+struct CullInstance {
+    model: mat4x4<f32>,
+    bounds_min: vec3<f32>,
+    batch: u32,
+    bounds_max: vec3<f32>,
+    padding: u32,
+}
+
+// laid out like wgpu::util::DrawIndexedIndirectArgs
+struct DrawArgs {
+    index_count: u32,
+    instance_count: atomic<u32>,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+// planes face inwards: left, right, bottom, top, near, far
+struct CullParams {
+    view_proj: mat4x4<f32>,
+    planes: array<vec4<f32>, 6>,
+    instance_count: u32,
+    hi_z: u32,
+    hi_z_mips: u32,
+    padding: u32,
+    hi_z_size: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<storage, read> instances: array<CullInstance>;
+
+// where each batch's range of `visible` starts
+@group(0) @binding(1)
+var<storage, read> batch_starts: array<u32>;
+
+@group(0) @binding(2)
+var<storage, read_write> draws: array<DrawArgs>;
+
+@group(0) @binding(3)
+var<storage, read_write> visible: array<u32>;
+
+@group(0) @binding(4)
+var<uniform> params: CullParams;
+
+// farthest depth of each texel's footprint, one mip per halving
+@group(0) @binding(5)
+var hi_z: texture_2d<f32>;
+
+// the world space box is tested through its 8 corners, anything reaching behind the camera is kept
+fn occluded(center: vec3<f32>, extents: vec3<f32>) -> bool {
+    var ndc_min = vec3<f32>(1e30);
+    var ndc_max = vec3<f32>(-1e30);
+    for (var i = 0u; i < 8u; i++) {
+        let corner = center + extents * vec3<f32>(
+            select(-1.0, 1.0, (i & 1u) != 0u),
+            select(-1.0, 1.0, (i & 2u) != 0u),
+            select(-1.0, 1.0, (i & 4u) != 0u),
+        );
+        let clip = params.view_proj * vec4<f32>(corner, 1.0);
+        if clip.w <= 0.0 {
+            return false;
+        }
+        let ndc = clip.xyz / clip.w;
+        ndc_min = min(ndc_min, ndc);
+        ndc_max = max(ndc_max, ndc);
+    }
+
+    let uv_min = clamp(vec2<f32>(ndc_min.x, -ndc_max.y) * 0.5 + 0.5, vec2<f32>(0.0), vec2<f32>(1.0));
+    let uv_max = clamp(vec2<f32>(ndc_max.x, -ndc_min.y) * 0.5 + 0.5, vec2<f32>(0.0), vec2<f32>(1.0));
+
+    // the mip where the box covers at most 2x2 texels
+    let size = (uv_max - uv_min) * params.hi_z_size;
+    let mip = i32(min(u32(ceil(log2(max(max(size.x, size.y), 1.0)))), params.hi_z_mips - 1u));
+    let mip_size = vec2<i32>(textureDimensions(hi_z, mip));
+    let low = clamp(vec2<i32>(uv_min * vec2<f32>(mip_size)), vec2<i32>(0), mip_size - 1);
+    let high = clamp(vec2<i32>(uv_max * vec2<f32>(mip_size)), vec2<i32>(0), mip_size - 1);
+
+    let depth = max(
+        max(textureLoad(hi_z, low, mip).r, textureLoad(hi_z, vec2<i32>(high.x, low.y), mip).r),
+        max(textureLoad(hi_z, vec2<i32>(low.x, high.y), mip).r, textureLoad(hi_z, high, mip).r),
+    );
+    return ndc_min.z > depth;
+}
+
+@compute @workgroup_size(64)
+fn cull(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if index >= params.instance_count {
+        return;
+    }
+    let instance = instances[index];
+
+    // world space box around the transformed model space box, like Aabb::transform
+    let local_center = (instance.bounds_min + instance.bounds_max) * 0.5;
+    let local_extents = (instance.bounds_max - instance.bounds_min) * 0.5;
+    let center = (instance.model * vec4<f32>(local_center, 1.0)).xyz;
+    let extents = abs(instance.model[0].xyz) * local_extents.x
+        + abs(instance.model[1].xyz) * local_extents.y
+        + abs(instance.model[2].xyz) * local_extents.z;
+
+    for (var i = 0u; i < 6u; i++) {
+        let plane = params.planes[i];
+        if dot(plane.xyz, center) + plane.w < -dot(abs(plane.xyz), extents) {
+            return;
+        }
+    }
+
+    if params.hi_z != 0u && occluded(center, extents) {
+        return;
+    }
+
+    let slot = atomicAdd(&draws[instance.batch].instance_count, 1u);
+    visible[batch_starts[instance.batch] + slot] = index;
+}
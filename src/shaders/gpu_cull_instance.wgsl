@@ -0,0 +1,28 @@
+// instance data for draws issued by GpuCullingPass::draw, the bind group is at @group(0)
+
+struct CullInstance {
+    model: mat4x4<f32>,
+    bounds_min: vec3<f32>,
+    batch: u32,
+    bounds_max: vec3<f32>,
+    padding: u32,
+}
+
+// start of the batch's visible range, 0 when the indirect draw's first instance already has it
+struct CullDraw {
+    first: u32,
+}
+
+@group(0) @binding(0)
+var<storage, read> cull_instances: array<CullInstance>;
+
+@group(0) @binding(1)
+var<storage, read> cull_visible: array<u32>;
+
+@group(0) @binding(2)
+var<uniform> cull_draw: CullDraw;
+
+// the visible instance drawn as `instance_index`
+fn culled_instance(instance_index: u32) -> CullInstance {
+    return cull_instances[cull_visible[cull_draw.first + instance_index]];
+}
@@ -0,0 +1,35 @@
+// appended to fullscreen.wgsl, renders mip 0 of the Hi-Z pyramid from the depth texture.
+// the depth is read as an unfilterable float texture, swapped for a multisampled one with MSAA
+// and SAMPLES replaced by the sample count
+
+const SAMPLES: i32 = 1;
+
+@group(0) @binding(0)
+var depth: texture_2d<f32>;
+
+// the pyramid is the depth size rounded up to powers of two
+fn pyramid_size(size: vec2<u32>) -> vec2<u32> {
+    return vec2<u32>(1u) << (32u - countLeadingZeros(max(size, vec2<u32>(1u)) - 1u));
+}
+
+@fragment
+fn fs_copy_depth(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let depth_size = textureDimensions(depth);
+    let size = pyramid_size(depth_size);
+    let texel = vec2<u32>(in.clip_position.xy);
+
+    // every depth pixel this texel overlaps, at most 2x2 since the pyramid is at least as large
+    let first = texel * depth_size / size;
+    let last = min(((texel + 1u) * depth_size + size - 1u) / size, depth_size) - 1u;
+
+    var value = 0.0;
+    for (var y = first.y; y <= last.y; y++) {
+        for (var x = first.x; x <= last.x; x++) {
+            // the farthest sample, anything nearer could be covering only part of the pixel
+            for (var sample = 0; sample < SAMPLES; sample++) {
+                value = max(value, textureLoad(depth, vec2<u32>(x, y), sample).r);
+            }
+        }
+    }
+    return vec4<f32>(value, 0.0, 0.0, 0.0);
+}
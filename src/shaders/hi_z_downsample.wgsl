@@ -0,0 +1,26 @@
+// appended to fullscreen.wgsl, renders one Hi-Z mip from the one above keeping the farthest depth
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+
+@fragment
+fn fs_downsample(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let source_size = vec2<i32>(textureDimensions(source));
+    let size = max(source_size / 2, vec2<i32>(1));
+    let texel = vec2<i32>(in.clip_position.xy);
+
+    // an odd sized source folds its last row and column into the texels next to them
+    let base = texel * 2;
+    let last = vec2<i32>(
+        select(base.x + 1, source_size.x - 1, texel.x == size.x - 1),
+        select(base.y + 1, source_size.y - 1, texel.y == size.y - 1),
+    );
+
+    var value = 0.0;
+    for (var y = base.y; y <= last.y; y++) {
+        for (var x = base.x; x <= last.x; x++) {
+            value = max(value, textureLoad(source, min(vec2<i32>(x, y), source_size - 1), 0).r);
+        }
+    }
+    return vec4<f32>(value, 0.0, 0.0, 0.0);
+}
@@ -0,0 +1,35 @@
+// image based lighting, prepended with `ibl::shader_snippet(group)`
+
+@group(0) @binding(0)
+var ibl_irradiance: texture_cube<f32>;
+
+@group(0) @binding(1)
+var ibl_prefiltered: texture_cube<f32>;
+
+@group(0) @binding(2)
+var ibl_brdf_lut: texture_2d<f32>;
+
+@group(0) @binding(3)
+var ibl_sampler: sampler;
+
+fn fresnel_schlick_roughness(cos_theta: f32, f0: vec3<f32>, roughness: f32) -> vec3<f32> {
+    return f0 + (max(vec3<f32>(1.0 - roughness), f0) - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+// light the environment reflects towards the viewer, `view` points from the surface to the camera
+fn ibl_ambient(normal: vec3<f32>, view: vec3<f32>, albedo: vec3<f32>, roughness: f32, metallic: f32) -> vec3<f32> {
+    let n_dot_v = max(dot(normal, view), 0.0);
+    let f0 = mix(vec3<f32>(0.04), albedo, metallic);
+    let f = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+
+    let kd = (1.0 - f) * (1.0 - metallic);
+    let diffuse = textureSampleLevel(ibl_irradiance, ibl_sampler, normal, 0.0).rgb * albedo;
+
+    let reflected = reflect(-view, normal);
+    let max_mip = f32(textureNumLevels(ibl_prefiltered) - 1u);
+    let prefiltered = textureSampleLevel(ibl_prefiltered, ibl_sampler, reflected, roughness * max_mip).rgb;
+    let brdf = textureSampleLevel(ibl_brdf_lut, ibl_sampler, vec2<f32>(n_dot_v, roughness), 0.0).rg;
+    let specular = prefiltered * (f * brdf.x + brdf.y);
+
+    return kd * diffuse + specular;
+}
@@ -0,0 +1,194 @@
+// precomputes image based lighting from an environment cube map:
+// diffuse irradiance, GGX prefiltered specular (one dispatch per mip) and the split sum BRDF LUT
+
+const PI: f32 = 3.14159265359;
+const PREFILTER_SAMPLES: u32 = 512u;
+const BRDF_SAMPLES: u32 = 1024u;
+
+struct PrefilterParams {
+    roughness: f32,
+    // resolution of the environment's first mip, for the sample mip level
+    environment_size: f32,
+    _padding: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var environment: texture_cube<f32>;
+
+@group(0) @binding(1)
+var environment_sampler: sampler;
+
+@group(0) @binding(2)
+var irradiance_out: texture_storage_2d_array<rgba16float, write>;
+
+@group(0) @binding(3)
+var prefiltered_out: texture_storage_2d_array<rgba16float, write>;
+
+@group(0) @binding(4)
+var<uniform> params: PrefilterParams;
+
+@group(0) @binding(5)
+var brdf_lut_out: texture_storage_2d<rgba16float, write>;
+
+// direction through texel `uv` (-1..1, v pointing down) of cube face `face`, in +X, -X, +Y, -Y, +Z, -Z order
+fn cube_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    switch face {
+        case 0u: { return normalize(vec3<f32>(1.0, -uv.y, -uv.x)); }
+        case 1u: { return normalize(vec3<f32>(-1.0, -uv.y, uv.x)); }
+        case 2u: { return normalize(vec3<f32>(uv.x, 1.0, uv.y)); }
+        case 3u: { return normalize(vec3<f32>(uv.x, -1.0, -uv.y)); }
+        case 4u: { return normalize(vec3<f32>(uv.x, -uv.y, 1.0)); }
+        default: { return normalize(vec3<f32>(-uv.x, -uv.y, -1.0)); }
+    }
+}
+
+fn texel_direction(id: vec3<u32>, size: vec2<u32>) -> vec3<f32> {
+    let uv = (vec2<f32>(id.xy) + 0.5) / vec2<f32>(size) * 2.0 - 1.0;
+    return cube_direction(id.z, uv);
+}
+
+// tangent frame around `n`
+fn tangent_to_world(v: vec3<f32>, n: vec3<f32>) -> vec3<f32> {
+    let up = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(n.y) < 0.999);
+    let tangent = normalize(cross(up, n));
+    let bitangent = cross(n, tangent);
+    return tangent * v.x + bitangent * v.y + n * v.z;
+}
+
+fn radical_inverse(bits_in: u32) -> f32 {
+    var bits = bits_in;
+    bits = (bits << 16u) | (bits >> 16u);
+    bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+    bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+    bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+    bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+    return f32(bits) * 2.3283064365386963e-10;
+}
+
+fn hammersley(i: u32, count: u32) -> vec2<f32> {
+    return vec2<f32>(f32(i) / f32(count), radical_inverse(i));
+}
+
+// GGX distributed half vector around `n`
+fn importance_sample_ggx(xi: vec2<f32>, n: vec3<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return tangent_to_world(vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta), n);
+}
+
+fn distribution_ggx(n_dot_h: f32, roughness: f32) -> f32 {
+    let a = roughness * roughness;
+    let a2 = a * a;
+    let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+fn geometry_schlick_ggx(n_dot_v: f32, roughness: f32) -> f32 {
+    // k for image based lighting
+    let k = roughness * roughness / 2.0;
+    return n_dot_v / (n_dot_v * (1.0 - k) + k);
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn irradiance(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(irradiance_out).xy;
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    let normal = texel_direction(id, size);
+
+    // cosine weighted integral over the hemisphere
+    let delta = 0.05;
+    var sum = vec3<f32>(0.0);
+    var count = 0.0;
+    for (var phi = 0.0; phi < 2.0 * PI; phi += delta) {
+        for (var theta = 0.0; theta < 0.5 * PI; theta += delta) {
+            let tangent_sample = vec3<f32>(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+            let direction = tangent_to_world(tangent_sample, normal);
+            // a coarser mip keeps the few samples from aliasing
+            sum += textureSampleLevel(environment, environment_sampler, direction, 2.0).rgb * cos(theta) * sin(theta);
+            count += 1.0;
+        }
+    }
+
+    textureStore(irradiance_out, id.xy, id.z, vec4<f32>(PI * sum / count, 1.0));
+}
+
+@compute
+@workgroup_size(8, 8, 1)
+fn prefilter(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(prefiltered_out).xy;
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    // view = normal = reflection, the usual split sum assumption
+    let n = texel_direction(id, size);
+    let roughness = params.roughness;
+
+    if (roughness == 0.0) {
+        textureStore(prefiltered_out, id.xy, id.z, vec4<f32>(textureSampleLevel(environment, environment_sampler, n, 0.0).rgb, 1.0));
+        return;
+    }
+
+    var sum = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < PREFILTER_SAMPLES; i++) {
+        let h = importance_sample_ggx(hammersley(i, PREFILTER_SAMPLES), n, roughness);
+        let l = normalize(2.0 * dot(n, h) * h - n);
+        let n_dot_l = dot(n, l);
+        if (n_dot_l > 0.0) {
+            // sample a mip matching the solid angle of the sample against that of a texel
+            let n_dot_h = max(dot(n, h), 0.0);
+            let pdf = distribution_ggx(n_dot_h, roughness) / 4.0 + 0.0001;
+            let sample_solid_angle = 1.0 / (f32(PREFILTER_SAMPLES) * pdf);
+            let texel_solid_angle = 4.0 * PI / (6.0 * params.environment_size * params.environment_size);
+            let mip = max(0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0, 0.0);
+
+            sum += textureSampleLevel(environment, environment_sampler, l, mip).rgb * n_dot_l;
+            weight += n_dot_l;
+        }
+    }
+
+    textureStore(prefiltered_out, id.xy, id.z, vec4<f32>(sum / max(weight, 0.0001), 1.0));
+}
+
+// x: scale, y: bias applied to F0 in the split sum approximation
+@compute
+@workgroup_size(8, 8, 1)
+fn brdf_lut(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(brdf_lut_out);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    let n_dot_v = max((f32(id.x) + 0.5) / f32(size.x), 0.001);
+    let roughness = (f32(id.y) + 0.5) / f32(size.y);
+    let v = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    let n = vec3<f32>(0.0, 0.0, 1.0);
+
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < BRDF_SAMPLES; i++) {
+        let h = importance_sample_ggx(hammersley(i, BRDF_SAMPLES), n, roughness);
+        let l = normalize(2.0 * dot(v, h) * h - v);
+
+        let n_dot_l = max(l.z, 0.0);
+        let n_dot_h = max(h.z, 0.0);
+        let v_dot_h = max(dot(v, h), 0.0);
+
+        if (n_dot_l > 0.0) {
+            let g = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+            let g_vis = g * v_dot_h / (n_dot_h * n_dot_v);
+            let fc = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fc) * g_vis;
+            bias += fc * g_vis;
+        }
+    }
+
+    textureStore(brdf_lut_out, id.xy, vec4<f32>(scale / f32(BRDF_SAMPLES), bias / f32(BRDF_SAMPLES), 0.0, 1.0));
+}
@@ -0,0 +1,206 @@
+const MAX_LIGHTS: u32 = 8u;
+const MAX_SHADOW_MAPS: u32 = 16u;
+const MAX_CASCADES: i32 = 4;
+
+const LIGHT_KIND_DIRECTIONAL: f32 = 0.0;
+const LIGHT_KIND_SPOT: f32 = 1.0;
+const LIGHT_KIND_POINT: f32 = 2.0;
+
+// position.w: kind, direction.w: shadow index, color.w: intensity
+// params: (range, cos inner angle, cos outer angle, unused)
+struct Light {
+    position: vec4<f32>,
+    direction: vec4<f32>,
+    color: vec4<f32>,
+    params: vec4<f32>,
+}
+
+struct Lights {
+    items: array<Light, MAX_LIGHTS>,
+    count: u32,
+}
+
+// params: (texel size, normal offset, pcf radius, point depth bias)
+// cascade_splits: view space depth at which each cascade ends
+// cascade_params: (cascade count, blend fraction, debug view, point shadow texel size)
+struct Shadows {
+    view_proj: array<mat4x4<f32>, MAX_SHADOW_MAPS>,
+    params: vec4<f32>,
+    cascade_splits: vec4<f32>,
+    cascade_params: vec4<f32>,
+    camera_position: vec4<f32>,
+    camera_forward: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> lights: Lights;
+
+@group(0) @binding(1)
+var<uniform> shadows: Shadows;
+
+@group(0) @binding(2)
+var shadow_map: texture_depth_2d_array;
+
+@group(0) @binding(3)
+var shadow_sampler: sampler_comparison;
+
+// `point_shadow_map` and `sample_point_shadow` come from `point_shadow_map.wgsl`, appended by
+// `ShadowPass::shader_snippet` when the pass has point shadows
+
+// 1.0 when fully lit, 0.0 when fully in shadow
+fn shadow_visibility(layer: i32, world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let offset_position = world_position + normal * shadows.params.y;
+    let clip = shadows.view_proj[layer] * vec4<f32>(offset_position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = ndc.xy * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
+
+    if (any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) || ndc.z > 1.0) {
+        return 1.0;
+    }
+
+    let radius = i32(shadows.params.z);
+    var visibility = 0.0;
+    var taps = 0.0;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * shadows.params.x;
+            visibility += textureSampleCompareLevel(shadow_map, shadow_sampler, uv + offset, layer, ndc.z);
+            taps += 1.0;
+        }
+    }
+
+    return visibility / taps;
+}
+
+fn point_shadow_visibility(cube: i32, light: Light, world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let offset = world_position + normal * shadows.params.y - light.position.xyz;
+    let distance = length(offset);
+    let depth = distance / light.params.x - shadows.params.w;
+
+    // PCF on the plane perpendicular to the lookup direction
+    let direction = offset / distance;
+    var up = vec3<f32>(0.0, 1.0, 0.0);
+    if (abs(direction.y) > 0.99) {
+        up = vec3<f32>(1.0, 0.0, 0.0);
+    }
+    let tangent = normalize(cross(up, direction));
+    let bitangent = cross(direction, tangent);
+
+    let radius = i32(shadows.params.z);
+    let spread = 2.0 * shadows.cascade_params.w;
+    var visibility = 0.0;
+    var taps = 0.0;
+    for (var y = -radius; y <= radius; y++) {
+        for (var x = -radius; x <= radius; x++) {
+            let sample_direction = direction + (tangent * f32(x) + bitangent * f32(y)) * spread;
+            visibility += sample_point_shadow(sample_direction, cube, depth);
+            taps += 1.0;
+        }
+    }
+
+    return visibility / taps;
+}
+
+fn view_depth(world_position: vec3<f32>) -> f32 {
+    return dot(world_position - shadows.camera_position.xyz, shadows.camera_forward.xyz);
+}
+
+// index of the cascade covering `world_position`, or -1 beyond the last one
+fn cascade_index(world_position: vec3<f32>) -> i32 {
+    let depth = view_depth(world_position);
+    let count = min(i32(shadows.cascade_params.x), MAX_CASCADES);
+    for (var i = 0; i < count; i++) {
+        if (depth < shadows.cascade_splits[i]) {
+            return i;
+        }
+    }
+    return -1;
+}
+
+fn cascaded_shadow_visibility(first_layer: i32, world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let cascade = cascade_index(world_position);
+    if (cascade < 0) {
+        return 1.0;
+    }
+
+    let visibility = shadow_visibility(first_layer + cascade, world_position, normal);
+
+    // fade into the next cascade over the last `blend` fraction of this one
+    let count = min(i32(shadows.cascade_params.x), MAX_CASCADES);
+    if (cascade + 1 >= count) {
+        return visibility;
+    }
+
+    var start = 0.0;
+    if (cascade > 0) {
+        start = shadows.cascade_splits[cascade - 1];
+    }
+    let end = shadows.cascade_splits[cascade];
+    let blend_start = end - (end - start) * shadows.cascade_params.y;
+    let t = smoothstep(blend_start, end, view_depth(world_position));
+    if (t <= 0.0) {
+        return visibility;
+    }
+
+    let next = shadow_visibility(first_layer + cascade + 1, world_position, normal);
+    return mix(visibility, next, t);
+}
+
+// tint to multiply the final color with, showing which cascade covers a fragment when the debug view is on
+fn cascade_debug_tint(world_position: vec3<f32>) -> vec3<f32> {
+    if (shadows.cascade_params.z == 0.0) {
+        return vec3<f32>(1.0);
+    }
+
+    switch cascade_index(world_position) {
+        case 0: { return vec3<f32>(1.0, 0.3, 0.3); }
+        case 1: { return vec3<f32>(0.3, 1.0, 0.3); }
+        case 2: { return vec3<f32>(0.3, 0.3, 1.0); }
+        case 3: { return vec3<f32>(1.0, 1.0, 0.3); }
+        default: { return vec3<f32>(1.0); }
+    }
+}
+
+fn light_radiance(light: Light, world_position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var to_light = -light.direction.xyz;
+    var attenuation = 1.0;
+
+    if (light.position.w != LIGHT_KIND_DIRECTIONAL) {
+        let offset = light.position.xyz - world_position;
+        let distance = length(offset);
+        to_light = offset / distance;
+
+        let range = light.params.x;
+        let falloff = clamp(1.0 - pow(distance / range, 4.0), 0.0, 1.0);
+        attenuation = falloff * falloff / (distance * distance + 1.0);
+    }
+
+    if (light.position.w == LIGHT_KIND_SPOT) {
+        attenuation *= smoothstep(light.params.z, light.params.y, dot(-to_light, light.direction.xyz));
+    }
+
+    let n_dot_l = max(dot(normal, to_light), 0.0);
+
+    let shadow_index = i32(light.direction.w);
+    var visibility = 1.0;
+    if (shadow_index >= 0) {
+        if (light.position.w == LIGHT_KIND_DIRECTIONAL) {
+            visibility = cascaded_shadow_visibility(shadow_index, world_position, normal);
+        } else if (light.position.w == LIGHT_KIND_POINT) {
+            visibility = point_shadow_visibility(shadow_index, light, world_position, normal);
+        } else {
+            visibility = shadow_visibility(shadow_index, world_position, normal);
+        }
+    }
+
+    return light.color.rgb * light.color.w * n_dot_l * attenuation * visibility;
+}
+
+// sum of the direct light reaching `world_position`, multiply with albedo for a lambertian surface
+fn shade(world_position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    var radiance = vec3<f32>(0.0);
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i++) {
+        radiance += light_radiance(lights.items[i], world_position, normal);
+    }
+    return radiance;
+}
@@ -0,0 +1,81 @@
+const HISTOGRAM_BINS: u32 = 256u;
+
+// adaptation: fraction of the way to move towards this frame's luminance
+struct HistogramParams {
+    min_log_luminance: f32,
+    log_luminance_range: f32,
+    adaptation: f32,
+    pixel_count: u32,
+}
+
+struct ExposureState {
+    luminance: f32,
+}
+
+@group(0) @binding(0)
+var hdr_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var<storage, read_write> histogram: array<atomic<u32>, HISTOGRAM_BINS>;
+
+@group(0) @binding(2)
+var<uniform> params: HistogramParams;
+
+@group(0) @binding(3)
+var<storage, read_write> exposure_state: ExposureState;
+
+var<workgroup> local_bins: array<atomic<u32>, HISTOGRAM_BINS>;
+var<workgroup> weighted_bins: array<f32, HISTOGRAM_BINS>;
+
+// bin 0 holds (nearly) black pixels, the rest is spread evenly over the log luminance range
+fn luminance_bin(color: vec3<f32>) -> u32 {
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    if (luminance < 0.0001) {
+        return 0u;
+    }
+
+    let t = clamp((log2(luminance) - params.min_log_luminance) / params.log_luminance_range, 0.0, 1.0);
+    return u32(t * 254.0 + 1.0);
+}
+
+@compute @workgroup_size(16, 16)
+fn build_histogram(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    atomicStore(&local_bins[local_index], 0u);
+    workgroupBarrier();
+
+    if (all(id.xy < textureDimensions(hdr_texture))) {
+        let color = textureLoad(hdr_texture, vec2<i32>(id.xy), 0).rgb;
+        atomicAdd(&local_bins[luminance_bin(color)], 1u);
+    }
+    workgroupBarrier();
+
+    atomicAdd(&histogram[local_index], atomicLoad(&local_bins[local_index]));
+}
+
+@compute @workgroup_size(256)
+fn average_histogram(@builtin(local_invocation_index) local_index: u32) {
+    let count = atomicLoad(&histogram[local_index]);
+    weighted_bins[local_index] = f32(count) * f32(local_index);
+    atomicStore(&histogram[local_index], 0u);
+    workgroupBarrier();
+
+    for (var stride = HISTOGRAM_BINS / 2u; stride > 0u; stride >>= 1u) {
+        if (local_index < stride) {
+            weighted_bins[local_index] += weighted_bins[local_index + stride];
+        }
+        workgroupBarrier();
+    }
+
+    if (local_index == 0u) {
+        // thread 0 holds the black bin, which is left out of the average
+        let lit_pixels = max(f32(params.pixel_count) - f32(count), 1.0);
+        let average_bin = weighted_bins[0] / lit_pixels - 1.0;
+        let log_luminance = average_bin / 254.0 * params.log_luminance_range + params.min_log_luminance;
+        let target_luminance = exp2(log_luminance);
+
+        exposure_state.luminance += (target_luminance - exposure_state.luminance) * params.adaptation;
+    }
+}
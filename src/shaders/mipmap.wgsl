@@ -0,0 +1,13 @@
+// prepended with fullscreen.wgsl, renders one mip from the one above
+
+@group(0) @binding(0)
+var source: texture_2d<f32>;
+
+@group(0) @binding(1)
+var source_sampler: sampler;
+
+// the linear sample in the middle of each 2x2 block averages it
+@fragment
+fn fs_downsample(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, in.uv);
+}
@@ -0,0 +1,36 @@
+struct PointShadow {
+    view_proj: array<mat4x4<f32>, 6>,
+    // xyz: light position, w: range
+    light: vec4<f32>,
+    face: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> point: PointShadow;
+
+struct ModelMatrix {
+    model: mat4x4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> model: ModelMatrix;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    let world_position = model.model * vec4<f32>(position, 1.0);
+    out.world_position = world_position.xyz;
+    out.clip_position = point.view_proj[point.face] * world_position;
+    return out;
+}
+
+// store the distance to the light instead of the projected depth, so it can be compared in any direction
+@fragment
+fn fs_main(in: VertexOutput) -> @builtin(frag_depth) f32 {
+    return length(in.world_position - point.light.xyz) / point.light.w;
+}
@@ -0,0 +1,6 @@
+@group(0) @binding(4)
+var point_shadow_map: texture_depth_cube_array;
+
+fn sample_point_shadow(direction: vec3<f32>, cube: i32, depth: f32) -> f32 {
+    return textureSampleCompareLevel(point_shadow_map, shadow_sampler, direction, cube, depth);
+}
@@ -0,0 +1,18 @@
+struct LightMatrix {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> light: LightMatrix;
+
+struct ModelMatrix {
+    model: mat4x4<f32>,
+}
+
+@group(1) @binding(0)
+var<uniform> model: ModelMatrix;
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>) -> @builtin(position) vec4<f32> {
+    return light.view_proj * model.model * vec4<f32>(position, 1.0);
+}
@@ -0,0 +1,120 @@
+// appended to fullscreen.wgsl
+
+// inv_view_proj: inverse of this frame's jittered view projection, the depth buffer was rendered with it
+struct TaaParams {
+    inv_view_proj: mat4x4<f32>,
+    unjittered_view_proj: mat4x4<f32>,
+    prev_view_proj: mat4x4<f32>,
+    // weight of the current frame
+    blend: f32,
+    // 1 when the history is invalid, e.g. after a resize
+    reset: u32,
+    _padding: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var current_texture: texture_2d<f32>;
+
+@group(0) @binding(1)
+var history_texture: texture_2d<f32>;
+
+@group(0) @binding(2)
+var motion_texture: texture_2d<f32>;
+
+@group(0) @binding(3)
+var linear_sampler: sampler;
+
+@group(0) @binding(4)
+var<uniform> params: TaaParams;
+
+@group(0) @binding(5)
+var depth_texture: texture_depth_2d;
+
+fn clip_to_uv(clip: vec4<f32>) -> vec2<f32> {
+    let ndc = clip.xy / clip.w;
+    return vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+}
+
+// camera motion only, reconstructed from depth: where this pixel was on screen last frame
+@fragment
+fn fs_motion(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let depth = textureLoad(depth_texture, vec2<i32>(in.clip_position.xy), 0);
+    let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+    let world = params.inv_view_proj * vec4<f32>(ndc, depth, 1.0);
+
+    let current = clip_to_uv(params.unjittered_view_proj * world);
+    let previous = clip_to_uv(params.prev_view_proj * world);
+    return vec4<f32>(current - previous, 0.0, 0.0);
+}
+
+fn rgb_to_ycocg(color: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        dot(color, vec3<f32>(0.25, 0.5, 0.25)),
+        dot(color, vec3<f32>(0.5, 0.0, -0.5)),
+        dot(color, vec3<f32>(-0.25, 0.5, -0.25)),
+    );
+}
+
+fn ycocg_to_rgb(color: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        color.x + color.y - color.z,
+        color.x + color.z,
+        color.x - color.y - color.z,
+    );
+}
+
+// HDR values are compressed before blending so single bright samples don't ghost
+fn compress(color: vec3<f32>) -> vec3<f32> {
+    return color / (1.0 + max(color.r, max(color.g, color.b)));
+}
+
+fn uncompress(color: vec3<f32>) -> vec3<f32> {
+    return color / max(1.0 - max(color.r, max(color.g, color.b)), 0.0001);
+}
+
+// clips the history towards the center of the neighborhood box instead of clamping per channel
+fn clip_to_box(history: vec3<f32>, box_min: vec3<f32>, box_max: vec3<f32>) -> vec3<f32> {
+    let center = 0.5 * (box_max + box_min);
+    let extents = 0.5 * (box_max - box_min) + 0.0001;
+    let offset = history - center;
+    let unit = abs(offset / extents);
+    let max_unit = max(unit.x, max(unit.y, unit.z));
+    if (max_unit > 1.0) {
+        return center + offset / max_unit;
+    }
+    return history;
+}
+
+@fragment
+fn fs_resolve(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let size = vec2<i32>(textureDimensions(current_texture));
+
+    let current = compress(textureLoad(current_texture, pixel, 0).rgb);
+    if (params.reset != 0u) {
+        return vec4<f32>(uncompress(current), 1.0);
+    }
+
+    // 3x3 neighborhood of the current frame in YCoCg
+    var box_min = rgb_to_ycocg(current);
+    var box_max = box_min;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let coord = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            let neighbor = rgb_to_ycocg(compress(textureLoad(current_texture, coord, 0).rgb));
+            box_min = min(box_min, neighbor);
+            box_max = max(box_max, neighbor);
+        }
+    }
+
+    let motion = textureLoad(motion_texture, pixel, 0).xy;
+    let history_uv = in.uv - motion;
+    let history_sample = textureSampleLevel(history_texture, linear_sampler, history_uv, 0.0).rgb;
+    let history = ycocg_to_rgb(clip_to_box(rgb_to_ycocg(compress(history_sample)), box_min, box_max));
+
+    // nothing to reproject from outside the screen
+    let on_screen = all(history_uv >= vec2<f32>(0.0)) && all(history_uv <= vec2<f32>(1.0));
+    let blend = select(1.0, params.blend, on_screen);
+
+    return vec4<f32>(uncompress(mix(history, current, blend)), 1.0);
+}
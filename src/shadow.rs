@@ -0,0 +1,897 @@
+use bytemuck::{Pod, Zeroable};
+use cgmath::*;
+
+use crate::camera::{Camera, Projection, OPENGL_TO_WGPU_MATRIX};
+use crate::light::*;
+use crate::object::{Object, Vertex};
+use crate::scene::Scene;
+use crate::texture;
+
+pub const MAX_SHADOW_MAPS: usize = 16;
+pub const MAX_CASCADES: usize = 4;
+
+// stands in for `point_shadow_map.wgsl` when the pass has no point shadow maps
+const NO_POINT_SHADOWS: &str = "
+fn sample_point_shadow(direction: vec3<f32>, cube: i32, depth: f32) -> f32 {
+    return 1.0;
+}
+";
+
+/// `resolution`, `layers` and the depth bias values are baked into the shadow map and its pipeline,
+/// the rest can be changed at runtime and is picked up by the next `update`
+pub struct ShadowSettings {
+    pub resolution: u32,
+    pub layers: u32,
+    pub constant_bias: i32,
+    pub slope_bias: f32,
+    pub normal_offset: f32,
+    pub pcf_radius: u32,
+    /// number of shadow map layers each directional light splits the camera frustum into
+    pub cascade_count: u32,
+    /// 0.0 splits the frustum uniformly, 1.0 logarithmically
+    pub cascade_split_lambda: f32,
+    /// fraction of each cascade over which it fades into the next one
+    pub cascade_blend: f32,
+    /// directional shadows end here, or at the projection's far plane if that is closer
+    pub shadow_distance: f32,
+    /// extends cascades towards the light so casters outside the view still cast into it
+    pub caster_margin: f32,
+    pub debug_cascades: bool,
+    pub point_resolution: u32,
+    /// number of point lights that can cast shadows at once, each takes one cube map
+    pub point_shadow_count: u32,
+    /// subtracted from the normalized light distance, point shadows write their own depth so the
+    /// pipeline depth bias doesn't apply
+    pub point_depth_bias: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 1024,
+            layers: 8,
+            constant_bias: 2,
+            slope_bias: 2.0,
+            normal_offset: 0.02,
+            pcf_radius: 1,
+            cascade_count: 4,
+            cascade_split_lambda: 0.75,
+            cascade_blend: 0.1,
+            shadow_distance: 100.0,
+            caster_margin: 20.0,
+            debug_cascades: false,
+            point_resolution: 512,
+            point_shadow_count: 2,
+            point_depth_bias: 0.005,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct ShadowUniform {
+    pub view_proj: [[[f32; 4]; 4]; MAX_SHADOW_MAPS],
+    /// (texel size, normal offset, pcf radius, point depth bias)
+    pub params: [f32; 4],
+    /// view space depth at which each cascade ends
+    pub cascade_splits: [f32; 4],
+    /// (cascade count, blend fraction, debug view, point shadow texel size)
+    pub cascade_params: [f32; 4],
+    pub camera_position: [f32; 4],
+    pub camera_forward: [f32; 4],
+}
+
+/// View space depths at which each cascade ends, blending uniform and logarithmic splits by `lambda`
+pub fn cascade_splits(znear: f32, zfar: f32, count: usize, lambda: f32) -> Vec<f32> {
+    (1..=count)
+        .map(|i| {
+            let p = i as f32 / count as f32;
+            let log = znear * (zfar / znear).powf(p);
+            let uniform = znear + (zfar - znear) * p;
+            lambda * log + (1.0 - lambda) * uniform
+        })
+        .collect()
+}
+
+/// Fits an orthographic light projection around the slice `znear..zfar` of the camera frustum.
+///
+/// The slice is enclosed in a bounding sphere so the projection size doesn't change as the camera
+/// rotates, and the projection is snapped to whole shadow map texels so it doesn't shimmer as
+/// the camera moves.
+pub fn cascade_view_proj(
+    light: &DirectionalLight,
+    camera: &Camera,
+    projection: &Projection,
+    znear: f32,
+    zfar: f32,
+    resolution: u32,
+    caster_margin: f32,
+) -> Matrix4<f32> {
+    let inverse = (projection.calc_matrix_with_depth(znear, zfar) * camera.calc_matrix())
+        .invert()
+        .unwrap_or(Matrix4::identity());
+
+    let mut corners = [Point3::origin(); 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let ndc = Vector4::new(
+            if i & 1 == 0 { -1.0 } else { 1.0 },
+            if i & 2 == 0 { -1.0 } else { 1.0 },
+            if i & 4 == 0 { 0.0 } else { 1.0 },
+            1.0,
+        );
+        let world = inverse * ndc;
+        *corner = Point3::from_homogeneous(world);
+    }
+
+    let center = Point3::centroid(&corners);
+    let radius = corners
+        .iter()
+        .map(|corner| corner.distance(center))
+        .fold(0.0, f32::max);
+    // quantize so floating point noise doesn't change the projection size from frame to frame
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let direction = light.direction.normalize();
+    let view = Matrix4::look_at_rh(center, center + direction, light_up(direction));
+    let mut proj = ortho(
+        -radius,
+        radius,
+        -radius,
+        radius,
+        -radius - caster_margin,
+        radius,
+    );
+
+    let half_resolution = resolution as f32 / 2.0;
+    let origin = (proj * view) * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    let origin = origin.truncate().truncate() * half_resolution;
+    let snap = Vector2::new(origin.x.round(), origin.y.round()) - origin;
+    proj.w.x += snap.x / half_resolution;
+    proj.w.y += snap.y / half_resolution;
+
+    OPENGL_TO_WGPU_MATRIX * proj * view
+}
+
+pub fn spot_view_proj(light: &SpotLight) -> Matrix4<f32> {
+    let direction = light.direction.normalize();
+    let view = Matrix4::look_at_rh(
+        light.position,
+        light.position + direction,
+        light_up(direction),
+    );
+    let proj = perspective(light.outer_angle * 2.0, 1.0, 0.05, light.range);
+
+    OPENGL_TO_WGPU_MATRIX * proj * view
+}
+
+/// (look direction, up) of each cube map face in `+X, -X, +Y, -Y, +Z, -Z` order
+const CUBE_FACES: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+];
+
+/// One 90 degree view per cube map face, mirrored in x since cube maps are addressed as seen
+/// from inside the cube
+pub fn point_view_projs(light: &PointLight) -> [Matrix4<f32>; 6] {
+    let proj = Matrix4::from_nonuniform_scale(-1.0, 1.0, 1.0)
+        * OPENGL_TO_WGPU_MATRIX
+        * perspective(Deg(90.0), 1.0, 0.05, light.range);
+
+    CUBE_FACES.map(|(direction, up)| {
+        let view = Matrix4::look_at_rh(
+            light.position,
+            light.position + Vector3::from(direction),
+            up.into(),
+        );
+        proj * view
+    })
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct PointShadowUniform {
+    view_proj: [[[f32; 4]; 4]; 6],
+    /// xyz: light position, w: range
+    light: [f32; 4],
+    /// face to render when not using layered rendering
+    face: u32,
+    _padding: [u32; 3],
+}
+
+// look_at breaks down when looking straight along the up vector
+fn light_up(direction: Vector3<f32>) -> Vector3<f32> {
+    if direction.y.abs() > 0.99 {
+        Vector3::unit_z()
+    } else {
+        Vector3::unit_y()
+    }
+}
+
+/// Renders scene depth from every shadow casting light into one layer of a depth texture array,
+/// and the distance to every shadow casting point light into a cube of a depth cube map array.
+/// Cube faces are rendered in a single layered pass when the device has `Features::MULTIVIEW`.
+/// Adapters without cube map arrays get no point light shadows, see `point_shadows`.
+///
+/// `bind_group` holds everything a lit shader needs (see `shader_snippet`):
+/// - binding 0 : `LightsUniform`
+/// - binding 1 : `ShadowUniform`
+/// - binding 2 : shadow map, `texture_depth_2d_array`
+/// - binding 3 : comparison sampler
+/// - binding 4 : point shadow maps, `texture_depth_cube_array`, only with `point_shadows`
+pub struct ShadowPass {
+    pub settings: ShadowSettings,
+    pub shadow_map: texture::Texture,
+    layer_views: Vec<wgpu::TextureView>,
+    active_layers: u32,
+    pipeline: wgpu::RenderPipeline,
+    uniform_alignment: wgpu::BufferAddress,
+    light_matrix_buffer: wgpu::Buffer,
+    light_matrix_bind_group: wgpu::BindGroup,
+    model_bind_group_layout: wgpu::BindGroupLayout,
+    model_buffer: wgpu::Buffer,
+    model_bind_group: wgpu::BindGroup,
+    model_capacity: usize,
+    pub point_shadow_map: Option<texture::Texture>,
+    point_views: Vec<wgpu::TextureView>,
+    active_point_shadows: u32,
+    multiview: bool,
+    point_pipeline: wgpu::RenderPipeline,
+    point_uniform_stride: wgpu::BufferAddress,
+    point_uniform_buffer: wgpu::Buffer,
+    point_uniform_bind_group: wgpu::BindGroup,
+    pub lights_buffer: wgpu::Buffer,
+    pub shadow_buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl ShadowPass {
+    /// Point light shadows need cube map arrays, without `DownlevelFlags::CUBE_ARRAY_TEXTURES`
+    /// point lights are left unshadowed. `downlevel` comes from `Engine::downlevel_capabilities()`
+    pub fn new(
+        device: &wgpu::Device,
+        downlevel: &wgpu::DownlevelCapabilities,
+        settings: ShadowSettings,
+    ) -> Self {
+        let point_shadows = downlevel
+            .flags
+            .contains(wgpu::DownlevelFlags::CUBE_ARRAY_TEXTURES);
+
+        let layers = settings.layers.clamp(1, MAX_SHADOW_MAPS as u32);
+        let shadow_map = texture::Texture::create_depth_texture_array(
+            device,
+            settings.resolution,
+            layers,
+            Some("Shadow Map"),
+        );
+        let layer_views = (0..layers)
+            .map(|layer| shadow_map.create_layer_view(layer))
+            .collect();
+
+        let uniform_alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let matrix_size = std::mem::size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress;
+
+        let dynamic_matrix_layout = |label| {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some(label),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(matrix_size),
+                    },
+                    count: None,
+                }],
+            })
+        };
+        let light_matrix_bind_group_layout = dynamic_matrix_layout("Shadow Light Layout");
+        let model_bind_group_layout = dynamic_matrix_layout("Shadow Model Layout");
+
+        let light_matrix_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Light Matrices"),
+            size: uniform_alignment * MAX_SHADOW_MAPS as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let light_matrix_bind_group = Self::create_dynamic_bind_group(
+            device,
+            &light_matrix_bind_group_layout,
+            &light_matrix_buffer,
+            matrix_size,
+        );
+
+        let model_capacity = 16;
+        let model_buffer = Self::create_model_buffer(device, uniform_alignment, model_capacity);
+        let model_bind_group = Self::create_dynamic_bind_group(
+            device,
+            &model_bind_group_layout,
+            &model_buffer,
+            matrix_size,
+        );
+
+        let multiview = device.features().contains(wgpu::Features::MULTIVIEW);
+        let point_shadow_count = settings.point_shadow_count.max(1);
+        let point_shadow_map = point_shadows.then(|| {
+            texture::Texture::create_depth_cube_array(
+                device,
+                settings.point_resolution,
+                point_shadow_count,
+                Some("Point Shadow Map"),
+            )
+        });
+        let point_views = match &point_shadow_map {
+            Some(point_shadow_map) if multiview => (0..point_shadow_count)
+                .map(|cube| point_shadow_map.create_layer_range_view(cube * 6, 6))
+                .collect(),
+            Some(point_shadow_map) => (0..point_shadow_count * 6)
+                .map(|layer| point_shadow_map.create_layer_view(layer))
+                .collect(),
+            None => vec![],
+        };
+
+        let point_uniform_size = std::mem::size_of::<PointShadowUniform>() as wgpu::BufferAddress;
+        let point_uniform_stride = wgpu::util::align_to(point_uniform_size, uniform_alignment);
+        let point_uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Point Shadow Layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(point_uniform_size),
+                    },
+                    count: None,
+                }],
+            });
+        let point_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Point Shadow Matrices"),
+            size: point_uniform_stride * point_shadow_count as u64 * 6,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let point_uniform_bind_group = Self::create_dynamic_bind_group(
+            device,
+            &point_uniform_bind_group_layout,
+            &point_uniform_buffer,
+            point_uniform_size,
+        );
+
+        let lights_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lights"),
+            size: std::mem::size_of::<LightsUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let shadow_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadows"),
+            size: std::mem::size_of::<ShadowUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut layout_entries = vec![
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 3,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                count: None,
+            },
+        ];
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: lights_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: shadow_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: wgpu::BindingResource::TextureView(&shadow_map.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: wgpu::BindingResource::Sampler(&shadow_map.sampler),
+            },
+        ];
+        if let Some(point_shadow_map) = &point_shadow_map {
+            layout_entries.push(wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension: wgpu::TextureViewDimension::CubeArray,
+                    multisampled: false,
+                },
+                count: None,
+            });
+            entries.push(wgpu::BindGroupEntry {
+                binding: 4,
+                resource: wgpu::BindingResource::TextureView(&point_shadow_map.view),
+            });
+        }
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lighting Layout"),
+            entries: &layout_entries,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lighting Bind Group"),
+            layout: &bind_group_layout,
+            entries: &entries,
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/shadow_depth.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[&light_matrix_bind_group_layout, &model_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc()],
+            },
+            fragment: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // single sided geometry such as planes should cast shadows as well
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState {
+                    constant: settings.constant_bias,
+                    slope_scale: settings.slope_bias,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        let point_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Point Shadow Shader"),
+            source: wgpu::ShaderSource::Wgsl(if multiview {
+                include_str!("shaders/point_shadow_multiview.wgsl").into()
+            } else {
+                include_str!("shaders/point_shadow_depth.wgsl").into()
+            }),
+        });
+
+        let point_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Point Shadow Pipeline Layout"),
+                bind_group_layouts: &[&point_uniform_bind_group_layout, &model_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let point_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Point Shadow Pipeline"),
+            layout: Some(&point_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &point_shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc()],
+            },
+            // writes the normalized distance to the light as depth
+            fragment: Some(wgpu::FragmentState {
+                module: &point_shader,
+                entry_point: "fs_main",
+                targets: &[],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: if multiview {
+                std::num::NonZeroU32::new(6)
+            } else {
+                None
+            },
+        });
+
+        Self {
+            settings,
+            shadow_map,
+            layer_views,
+            active_layers: 0,
+            pipeline,
+            uniform_alignment,
+            light_matrix_buffer,
+            light_matrix_bind_group,
+            model_bind_group_layout,
+            model_buffer,
+            model_bind_group,
+            model_capacity,
+            point_shadow_map,
+            point_views,
+            active_point_shadows: 0,
+            multiview,
+            point_pipeline,
+            point_uniform_stride,
+            point_uniform_buffer,
+            point_uniform_bind_group,
+            lights_buffer,
+            shadow_buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    fn create_model_buffer(
+        device: &wgpu::Device,
+        alignment: wgpu::BufferAddress,
+        capacity: usize,
+    ) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Shadow Model Matrices"),
+            size: alignment * capacity as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_dynamic_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffer: &wgpu::Buffer,
+        size: wgpu::BufferAddress,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(size),
+                }),
+            }],
+        })
+    }
+
+    /// number of shadow map layers filled by the last `update`
+    pub fn active_layers(&self) -> u32 {
+        self.active_layers
+    }
+
+    /// number of point shadow cube maps filled by the last `update`
+    pub fn active_point_shadows(&self) -> u32 {
+        self.active_point_shadows
+    }
+
+    /// whether point lights cast shadows, which needs cube map arrays
+    pub fn point_shadows(&self) -> bool {
+        self.point_shadow_map.is_some()
+    }
+
+    /// whether cube faces are rendered in one layered pass
+    pub fn multiview(&self) -> bool {
+        self.multiview
+    }
+
+    fn write_point_shadow(&self, queue: &wgpu::Queue, cube: u32, light: &PointLight) {
+        let mut uniform = PointShadowUniform {
+            view_proj: point_view_projs(light).map(|view_proj| view_proj.into()),
+            light: [
+                light.position.x,
+                light.position.y,
+                light.position.z,
+                light.range,
+            ],
+            face: 0,
+            _padding: [0; 3],
+        };
+
+        if self.multiview {
+            queue.write_buffer(
+                &self.point_uniform_buffer,
+                self.point_uniform_stride * cube as u64,
+                bytemuck::cast_slice(&[uniform]),
+            );
+        } else {
+            for face in 0..6 {
+                uniform.face = face;
+                queue.write_buffer(
+                    &self.point_uniform_buffer,
+                    self.point_uniform_stride * (cube * 6 + face) as u64,
+                    bytemuck::cast_slice(&[uniform]),
+                );
+            }
+        }
+    }
+
+    /// Assigns shadow map layers to shadow casting lights and uploads light, shadow and model data.
+    /// Directional lights take `cascade_count` consecutive layers, spot lights one, point lights
+    /// a cube of the point shadow map.
+    /// Lights beyond `MAX_LIGHTS` are ignored, casters that don't fit in the remaining layers are
+    /// left unshadowed.
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, scene: &Scene) {
+        let settings = &self.settings;
+        let objects = &scene.objects;
+        let mut lights_uniform = LightsUniform::default();
+        let mut shadow_uniform = ShadowUniform::zeroed();
+        let mut layer = 0;
+        let mut point_shadows = 0;
+        let point_capacity = self
+            .point_shadow_map
+            .as_ref()
+            .map_or(0, |map| map.texture.depth_or_array_layers() / 6);
+
+        let cascade_count = (settings.cascade_count as usize).clamp(1, MAX_CASCADES);
+        let znear = scene.projection.znear();
+        let zfar = settings.shadow_distance.min(scene.projection.zfar());
+        let splits = cascade_splits(znear, zfar, cascade_count, settings.cascade_split_lambda);
+
+        for light in scene.lights.iter().take(MAX_LIGHTS) {
+            let view_projs = match (&light.kind, light.cast_shadow) {
+                (LightKind::Point(point), cast_shadow) => {
+                    if cast_shadow && point_shadows < point_capacity {
+                        self.write_point_shadow(queue, point_shadows, point);
+                        lights_uniform.push(light.to_raw(point_shadows as i32));
+                        point_shadows += 1;
+                    } else {
+                        lights_uniform.push(light.to_raw(-1));
+                    }
+                    continue;
+                }
+                (_, false) => vec![],
+                (LightKind::Directional(directional), true) => {
+                    let mut near = znear;
+                    splits
+                        .iter()
+                        .map(|&far| {
+                            let view_proj = cascade_view_proj(
+                                directional,
+                                &scene.camera,
+                                &scene.projection,
+                                near,
+                                far,
+                                settings.resolution,
+                                settings.caster_margin,
+                            );
+                            near = far;
+                            view_proj
+                        })
+                        .collect()
+                }
+                (LightKind::Spot(spot), true) => vec![spot_view_proj(spot)],
+            };
+
+            if !view_projs.is_empty() && layer + view_projs.len() <= self.layer_views.len() {
+                lights_uniform.push(light.to_raw(layer as i32));
+                for view_proj in view_projs {
+                    shadow_uniform.view_proj[layer] = view_proj.into();
+                    layer += 1;
+                }
+            } else {
+                lights_uniform.push(light.to_raw(-1));
+            }
+        }
+
+        let forward = scene.camera.forward();
+        shadow_uniform.cascade_splits[..cascade_count].copy_from_slice(&splits);
+        shadow_uniform.cascade_params = [
+            cascade_count as f32,
+            settings.cascade_blend,
+            if settings.debug_cascades { 1.0 } else { 0.0 },
+            1.0 / settings.point_resolution as f32,
+        ];
+        shadow_uniform.camera_position = scene.camera.position.to_homogeneous().into();
+        shadow_uniform.camera_forward = forward.extend(0.0).into();
+        shadow_uniform.params = [
+            1.0 / settings.resolution as f32,
+            settings.normal_offset,
+            settings.pcf_radius as f32,
+            settings.point_depth_bias,
+        ];
+
+        for i in 0..layer {
+            queue.write_buffer(
+                &self.light_matrix_buffer,
+                self.uniform_alignment * i as u64,
+                bytemuck::cast_slice(&shadow_uniform.view_proj[i]),
+            );
+        }
+        queue.write_buffer(
+            &self.lights_buffer,
+            0,
+            bytemuck::cast_slice(&[lights_uniform]),
+        );
+        queue.write_buffer(
+            &self.shadow_buffer,
+            0,
+            bytemuck::cast_slice(&[shadow_uniform]),
+        );
+        self.active_layers = layer as u32;
+        self.active_point_shadows = point_shadows;
+
+        if objects.len() > self.model_capacity {
+            self.model_capacity = objects.len().next_power_of_two();
+            self.model_buffer =
+                Self::create_model_buffer(device, self.uniform_alignment, self.model_capacity);
+            self.model_bind_group = Self::create_dynamic_bind_group(
+                device,
+                &self.model_bind_group_layout,
+                &self.model_buffer,
+                std::mem::size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress,
+            );
+        }
+
+        for (i, object) in objects.iter().enumerate() {
+            let model: [[f32; 4]; 4] = object.model_matrix().into();
+            queue.write_buffer(
+                &self.model_buffer,
+                self.uniform_alignment * i as u64,
+                bytemuck::cast_slice(&model),
+            );
+        }
+    }
+
+    /// Records one depth-only pass per active shadow map layer and one (layered) or six passes per
+    /// active point shadow cube. Call before the main pass.
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, objects: &[Object]) {
+        for (layer, view) in self
+            .layer_views
+            .iter()
+            .enumerate()
+            .take(self.active_layers as usize)
+        {
+            let mut render_pass = Self::begin_depth_pass(encoder, view);
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(
+                0,
+                &self.light_matrix_bind_group,
+                &[(self.uniform_alignment * layer as u64) as u32],
+            );
+            self.draw_objects(&mut render_pass, objects);
+        }
+
+        let views_per_cube = if self.multiview { 1 } else { 6 };
+        for (i, view) in self
+            .point_views
+            .iter()
+            .enumerate()
+            .take((self.active_point_shadows * views_per_cube) as usize)
+        {
+            // uniforms are laid out per face in both cases, a layered pass uses the first one
+            let entry = if self.multiview { i * 6 } else { i };
+
+            let mut render_pass = Self::begin_depth_pass(encoder, view);
+            render_pass.set_pipeline(&self.point_pipeline);
+            render_pass.set_bind_group(
+                0,
+                &self.point_uniform_bind_group,
+                &[(self.point_uniform_stride * entry as u64) as u32],
+            );
+            self.draw_objects(&mut render_pass, objects);
+        }
+    }
+
+    /// WGSL declarations and functions for sampling `bind_group`, placed at bind group `group`.
+    /// Prepend it to a shader source to get `shadow_visibility` and `shade`.
+    pub fn shader_snippet(&self, group: u32) -> String {
+        let point_shadow_map = if self.point_shadows() {
+            include_str!("shaders/point_shadow_map.wgsl")
+        } else {
+            NO_POINT_SHADOWS
+        };
+        (include_str!("shaders/lighting.wgsl").to_string() + point_shadow_map)
+            .replace("@group(0)", &format!("@group({})", group))
+    }
+
+    fn begin_depth_pass<'a>(
+        encoder: &'a mut wgpu::CommandEncoder,
+        view: &'a wgpu::TextureView,
+    ) -> wgpu::RenderPass<'a> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        })
+    }
+
+    fn draw_objects<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, objects: &'a [Object]) {
+        for (i, object) in objects.iter().enumerate().take(self.model_capacity) {
+            render_pass.set_bind_group(
+                1,
+                &self.model_bind_group,
+                &[(self.uniform_alignment * i as u64) as u32],
+            );
+            render_pass.set_vertex_buffer(0, object.mesh.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(
+                object.mesh.index_buffer.slice(..),
+                wgpu::IndexFormat::Uint16,
+            );
+            render_pass.draw_indexed(0..object.mesh.indices.len() as u32, 0, 0..1);
+        }
+    }
+}
@@ -1,10 +1,63 @@
 use anyhow::*;
 use image::GenericImageView;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::sampler::{SamplerDesc, SamplerManager};
 
 pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
-    pub sampler: wgpu::Sampler,
+    /// Shared between textures created through a `SamplerManager`. This used to be a plain `wgpu::Sampler`,
+    /// code building a `Texture` itself wraps its sampler in `Arc::new`
+    pub sampler: Arc<wgpu::Sampler>,
+}
+
+/// What the values of an image mean, which decides whether sampling decodes them from sRGB
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// colors as authored, base color and emissive maps
+    #[default]
+    Srgb,
+    /// data used as is, normal, metallic/roughness and occlusion maps
+    Linear,
+}
+
+impl ColorSpace {
+    pub fn format(self) -> wgpu::TextureFormat {
+        match self {
+            ColorSpace::Srgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+            ColorSpace::Linear => wgpu::TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+/// Bits per channel of a float texture
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FloatPrecision {
+    /// `Rgba16Float`, filterable and renderable everywhere
+    #[default]
+    Half,
+    /// `Rgba32Float`
+    Full,
+}
+
+impl FloatPrecision {
+    pub fn format(self) -> wgpu::TextureFormat {
+        match self {
+            FloatPrecision::Half => wgpu::TextureFormat::Rgba16Float,
+            FloatPrecision::Full => wgpu::TextureFormat::Rgba32Float,
+        }
+    }
+}
+
+/// how `Texture::from_image_with_options` uploads an image
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TextureOptions {
+    /// allocate the full mip chain and fill it on the GPU, see `MipmapGenerator`
+    pub mipmaps: bool,
+    pub sampler: SamplerDesc,
+    pub color_space: ColorSpace,
 }
 
 impl Texture {
@@ -18,28 +71,138 @@ impl Texture {
         Self::from_image(device, queue, &img, label)
     }
 
+    pub fn from_bytes_with_options(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        bytes: &[u8],
+        options: &TextureOptions,
+        samplers: &mut SamplerManager,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let img = image::load_from_memory(bytes)?;
+        Self::from_image_with_options(device, queue, &img, options, samplers, label)
+    }
+
+    /// sRGB color with a single mip and the default sampler, see `from_image_with_options` for data maps,
+    /// mipmaps and other samplers
     pub fn from_image(
         device: &wgpu::Device,
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
     ) -> Result<Self> {
-        let rgba = img.to_rgba8();
-        let dimensions = img.dimensions();
+        let sampler = Arc::new(SamplerDesc::default().create(device));
+        let format = ColorSpace::Srgb.format();
+        Self::upload(device, queue, img.dimensions(), format, &img.to_rgba8(), None, sampler, label)
+    }
+
+    /// Uploads `img` with the mipmaps, sampler and color space from `options`.
+    /// The sampler comes from `samplers`, so textures with the same `SamplerDesc` share one
+    pub fn from_image_with_options(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        options: &TextureOptions,
+        samplers: &mut SamplerManager,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let sampler = samplers.get(device, &options.sampler);
+        let format = options.color_space.format();
+        let mipmaps = options.mipmaps.then_some(&mut samplers.mipmaps);
+        Self::upload(device, queue, img.dimensions(), format, &img.to_rgba8(), mipmaps, sampler, label)
+    }
+
+    /// Loads a Radiance `.hdr` or OpenEXR `.exr` file into a float texture, see `from_float_image`.
+    /// Each format needs its cargo feature, `hdr` or `exr`
+    pub fn from_float_file<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: P,
+        precision: FloatPrecision,
+        options: &TextureOptions,
+        samplers: &mut SamplerManager,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+        if extension == "hdr" && !cfg!(feature = "hdr") {
+            bail!("can't load {}, built without the `hdr` feature", path.display());
+        }
+        if extension == "exr" && !cfg!(feature = "exr") {
+            bail!("can't load {}, built without the `exr` feature", path.display());
+        }
+
+        let img = image::open(path).with_context(|| format!("couldn't load {}", path.display()))?;
+        Self::from_float_image(device, queue, &img, precision, options, samplers, label)
+    }
+
+    /// Uploads `img` without clamping its values, for environment maps and other HDR content.
+    /// `options.color_space` is ignored, float textures are always linear. `Rgba32Float` isn't filterable
+    /// without `FLOAT32_FILTERABLE`, so it can't have mipmaps and needs a non filtering sampler
+    pub fn from_float_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        precision: FloatPrecision,
+        options: &TextureOptions,
+        samplers: &mut SamplerManager,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let rgba = img.to_rgba32f();
+        let sampler = samplers.get(device, &options.sampler);
+        let format = precision.format();
+
+        match precision {
+            FloatPrecision::Half => {
+                let half: Vec<half::f16> = rgba.iter().map(|v| half::f16::from_f32(*v)).collect();
+                let data = bytemuck::cast_slice(&half);
+                let mipmaps = options.mipmaps.then_some(&mut samplers.mipmaps);
+                Self::upload(device, queue, img.dimensions(), format, data, mipmaps, sampler, label)
+            }
+            FloatPrecision::Full => {
+                if options.mipmaps {
+                    bail!("Rgba32Float isn't filterable, mipmaps need FloatPrecision::Half");
+                }
+                let data = bytemuck::cast_slice(&rgba);
+                Self::upload(device, queue, img.dimensions(), format, data, None, sampler, label)
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn upload(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        dimensions: (u32, u32),
+        format: wgpu::TextureFormat,
+        data: &[u8],
+        mipmaps: Option<&mut MipmapGenerator>,
+        sampler: Arc<wgpu::Sampler>,
+        label: Option<&str>,
+    ) -> Result<Self> {
 
         let size = wgpu::Extent3d {
             width: dimensions.0,
             height: dimensions.1,
             depth_or_array_layers: 1,
         };
+        let mip_level_count = if mipmaps.is_some() {
+            Self::mip_level_count(dimensions.0, dimensions.1)
+        } else {
+            1
+        };
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        if mip_level_count > 1 {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
-            mip_level_count: 1,
+            mip_level_count,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            format,
+            usage,
             view_formats: &[],
         });
 
@@ -50,39 +213,462 @@ impl Texture {
                 origin: wgpu::Origin3d::ZERO,
                 aspect: wgpu::TextureAspect::All,
             },
-            bytemuck::cast_slice(&rgba),
+            data,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
+                bytes_per_row: Some(format.block_copy_size(None).unwrap() * dimensions.0),
                 rows_per_image: Some(dimensions.1),
             },
             size,
         );
+        if let Some(mipmaps) = mipmaps {
+            mipmaps.generate(device, queue, &texture);
+        }
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
+
+    /// number of mips down to 1x1
+    pub fn mip_level_count(width: u32, height: u32) -> u32 {
+        32 - width.max(height).max(1).leading_zeros()
+    }
+
+    /// 2D array texture with one layer per image, sampled as `texture_2d_array` so all of them share a bind group.
+    /// The images have to be the same size, mipmaps and color space come from `options`
+    pub fn array_from_images(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[&image::DynamicImage],
+        options: &TextureOptions,
+        samplers: &mut SamplerManager,
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let Some(first) = images.first() else {
+            bail!("texture array needs at least one image");
+        };
+        let (width, height) = first.dimensions();
+        if let Some(image) = images.iter().find(|image| image.dimensions() != (width, height)) {
+            let (image_width, image_height) = image.dimensions();
+            bail!(
+                "texture array images have different sizes, {}x{} and {}x{}",
+                width,
+                height,
+                image_width,
+                image_height
+            );
+        }
+
+        let mip_level_count = if options.mipmaps {
+            Self::mip_level_count(width, height)
+        } else {
+            1
+        };
+        let mut usage = wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+        if mip_level_count > 1 {
+            usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+        }
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: images.len() as u32,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: options.color_space.format(),
+            usage,
+            view_formats: &[],
+        });
+
+        for (layer, image) in images.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &image.to_rgba8(),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        samplers.mipmaps.generate(device, queue, &texture);
+
+        // explicit, a single layer would otherwise get a D2 view
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler: samplers.get(device, &options.sampler),
+        })
+    }
+
+    /// Empty cube map, `size` x `size` per face. The view is a `Cube` view over all mips,
+    /// faces are layers in `+X, -X, +Y, -Y, +Z, -Z` order
+    pub fn create_cube(
+        device: &wgpu::Device,
+        size: u32,
+        mip_level_count: u32,
+        format: wgpu::TextureFormat,
+        usage: wgpu::TextureUsages,
+        label: Option<&str>,
+    ) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = Arc::new(device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        }));
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// cube map from six square images of the same size, in `+X, -X, +Y, -Y, +Z, -Z` order
+    pub fn cube_from_images(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        faces: [&image::DynamicImage; 6],
+        label: Option<&str>,
+    ) -> Result<Self> {
+        let (width, height) = faces[0].dimensions();
+        if width != height {
+            bail!("cube map faces have to be square, got {}x{}", width, height);
+        }
+        if let Some(face) = faces.iter().find(|face| face.dimensions() != (width, height)) {
+            let (face_width, face_height) = face.dimensions();
+            bail!(
+                "cube map faces have different sizes, {}x{} and {}x{}",
+                width,
+                height,
+                face_width,
+                face_height
+            );
+        }
+
+        let cube = Self::create_cube(
+            device,
+            width,
+            1,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC,
+            label,
+        );
+
+        for (layer, face) in faces.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &cube.texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &face.to_rgba8(),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        Ok(cube)
+    }
+
+    /// Cube map from an equirectangular (lat-long) image, converted on the GPU.
+    /// The result is `Rgba16Float` so HDR environments keep their range
+    pub fn cube_from_equirectangular(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        face_size: u32,
+        label: Option<&str>,
+    ) -> Self {
+        let (width, height) = img.dimensions();
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let equirect = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Equirectangular Source"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            equirect.as_image_copy(),
+            bytemuck::cast_slice(&img.to_rgba32f()),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(16 * width),
+                rows_per_image: Some(height),
+            },
+            size,
+        );
+
+        let cube = Self::create_cube(
+            device,
+            face_size,
+            1,
+            wgpu::TextureFormat::Rgba16Float,
+            wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            label,
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Equirect To Cube Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba16Float,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Equirect To Cube Bind Group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(
+                        &equirect.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&cube.create_layer_range_view(0, 6)),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Equirect To Cube Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/equirect_to_cube.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Equirect To Cube Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Equirect To Cube Pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "main",
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Equirect To Cube Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Equirect To Cube Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(face_size.div_ceil(8), face_size.div_ceil(8), 6);
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+
+        cube
+    }
+
+    /// color texture to render into and sample from afterwards, e.g. an HDR scene target
+    pub fn create_color_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: Option<&str>,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = Arc::new(device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
             mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Linear,
             mipmap_filter: wgpu::FilterMode::Nearest,
             ..Default::default()
+        }));
+
+        Self {
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    /// multisampled color target, rendered into and resolved to a single sampled view at the end of the pass
+    pub fn create_msaa_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: Option<&str>,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
         });
 
-        Ok(Self {
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        // can't be sampled, only here because every texture carries one
+        let sampler = Arc::new(device.create_sampler(&wgpu::SamplerDescriptor::default()));
+
+        Self {
             texture,
             view,
             sampler,
-        })
+        }
     }
 
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
     pub fn create_depth_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration, label: Option<&str>) -> Self {
+        Self::create_multisampled_depth_texture(device, config, 1, label)
+    }
+
+    /// depth texture matching a multisampled color target, `sample_count` has to be the same as the pipelines'
+    pub fn create_multisampled_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: Option<&str>,
+    ) -> Self {
+        Self::create_sized_depth_texture(device, config.width, config.height, sample_count, label)
+    }
+
+    /// depth texture of any size, for render targets that don't follow the window
+    pub fn create_sized_depth_texture(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+        label: Option<&str>,
+    ) -> Self {
         let size = wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
+            width,
+            height,
             depth_or_array_layers: 1,
         };
 
@@ -90,7 +676,7 @@ impl Texture {
             label,
             size,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: Self::DEPTH_FORMAT,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
@@ -100,7 +686,88 @@ impl Texture {
         let texture = device.create_texture(&desc);
         
         let view = texture.create_view(&Default::default());
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor{
+        let sampler = Self::create_comparison_sampler(device);
+        
+        Self { texture, view, sampler, }
+    }
+
+    pub fn create_depth_texture_array(device: &wgpu::Device, size: u32, layers: u32, label: Option<&str>) -> Self {
+        let size = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: layers,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let sampler = Self::create_comparison_sampler(device);
+
+        Self { texture, view, sampler, }
+    }
+
+    /// depth cube map array, sampled as `texture_depth_cube_array`.
+    /// faces are stored as 6 consecutive layers per cube in `+X, -X, +Y, -Y, +Z, -Z` order
+    pub fn create_depth_cube_array(device: &wgpu::Device, size: u32, cubes: u32, label: Option<&str>) -> Self {
+        let size = wgpu::Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: cubes * 6,
+        };
+
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::DEPTH_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::CubeArray),
+            ..Default::default()
+        });
+        let sampler = Self::create_comparison_sampler(device);
+
+        Self { texture, view, sampler, }
+    }
+
+    /// view into a single layer of an array texture, to be used as a render attachment
+    pub fn create_layer_view(&self, layer: u32) -> wgpu::TextureView {
+        self.create_layer_range_view(layer, 1)
+    }
+
+    /// view into `count` consecutive layers of an array texture, e.g. the 6 faces of one cube for layered rendering
+    pub fn create_layer_range_view(&self, base_layer: u32, count: u32) -> wgpu::TextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(if count == 1 {
+                wgpu::TextureViewDimension::D2
+            } else {
+                wgpu::TextureViewDimension::D2Array
+            }),
+            base_array_layer: base_layer,
+            array_layer_count: Some(count),
+            ..Default::default()
+        })
+    }
+
+    fn create_comparison_sampler(device: &wgpu::Device) -> Arc<wgpu::Sampler> {
+        Arc::new(device.create_sampler(&wgpu::SamplerDescriptor{
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
@@ -111,8 +778,184 @@ impl Texture {
             lod_max_clamp: 100.0,
             compare: Some(wgpu::CompareFunction::LessEqual),
             ..Default::default()
+        }))
+    }
+}
+
+struct MipmapShared {
+    shader: wgpu::ShaderModule,
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    sampler: wgpu::Sampler,
+}
+
+/// Fills mips 1.. of every layer by rendering each mip from the one above with a linear filter.
+/// The shader and layout are created on first use and a pipeline once per format, `SamplerManager`
+/// keeps one around since every texture constructor gets it anyway
+#[derive(Default)]
+pub struct MipmapGenerator {
+    shared: Option<MipmapShared>,
+    pipelines: Vec<(wgpu::TextureFormat, wgpu::RenderPipeline)>,
+}
+
+impl MipmapGenerator {
+    /// `texture` needs `RENDER_ATTACHMENT` usage and a filterable, renderable format.
+    /// Does nothing for a single mip
+    pub fn generate(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture) {
+        if texture.mip_level_count() < 2 {
+            return;
+        }
+
+        let shared = self.shared.get_or_insert_with(|| Self::create_shared(device));
+        let format = texture.format();
+        let pipeline = match self.pipelines.iter().position(|(f, _)| *f == format) {
+            Some(index) => &self.pipelines[index].1,
+            None => {
+                let pipeline = Self::create_pipeline(device, shared, format);
+                self.pipelines.push((format, pipeline));
+                &self.pipelines.last().unwrap().1
+            }
+        };
+
+        let mip_view = |layer, mip| {
+            texture.create_view(&wgpu::TextureViewDescriptor {
+                label: Some("Mip View"),
+                dimension: Some(wgpu::TextureViewDimension::D2),
+                base_mip_level: mip,
+                mip_level_count: Some(1),
+                base_array_layer: layer,
+                array_layer_count: Some(1),
+                ..Default::default()
+            })
+        };
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Mipmap Encoder"),
+        });
+        for layer in 0..texture.depth_or_array_layers() {
+            for mip in 1..texture.mip_level_count() {
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("Mipmap Bind Group"),
+                    layout: &shared.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&mip_view(layer, mip - 1)),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&shared.sampler),
+                        },
+                    ],
+                });
+
+                let target = mip_view(layer, mip);
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("Mipmap Pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &target,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                            store: wgpu::StoreOp::Store,
+                        },
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+                render_pass.set_pipeline(pipeline);
+                render_pass.set_bind_group(0, &bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    fn create_shared(device: &wgpu::Device) -> MipmapShared {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Mipmap Shader"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("shaders/fullscreen.wgsl"),
+                    include_str!("shaders/mipmap.wgsl")
+                )
+                .into(),
+            ),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Mipmap Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Mipmap Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
         });
-        
-        Self { texture, view, sampler, }
+
+        MipmapShared {
+            shader,
+            bind_group_layout,
+            pipeline_layout,
+            sampler,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        shared: &MipmapShared,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Mipmap Pipeline"),
+            layout: Some(&shared.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shared.shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shared.shader,
+                entry_point: "fs_downsample",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
     }
 }
@@ -0,0 +1,241 @@
+use crate::camera::{Camera, CameraUniform, Projection};
+use crate::render_target::RenderTargetManager;
+use crate::renderer::Renderer;
+
+/// Part of a target a camera draws into, in fractions of the target size with the origin at the top left
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+impl Viewport {
+    pub const FULL: Viewport = Viewport {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// `[x, y, width, height]` in pixels of a `target_width` x `target_height` target, at least
+    /// one pixel in size and inside the target
+    pub fn pixels(&self, target_width: u32, target_height: u32) -> [f32; 4] {
+        let (target_width, target_height) = (target_width as f32, target_height as f32);
+        let x = (self.x * target_width).clamp(0.0, (target_width - 1.0).max(0.0));
+        let y = (self.y * target_height).clamp(0.0, (target_height - 1.0).max(0.0));
+        [
+            x,
+            y,
+            (self.width * target_width).clamp(1.0, (target_width - x).max(1.0)),
+            (self.height * target_height).clamp(1.0, (target_height - y).max(1.0)),
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ViewTarget {
+    /// the window, or the HDR target in front of it
+    Window,
+    /// a render target from `Renderer::render_target_manager`, by id
+    Texture(u32),
+}
+
+/// What a camera clears before drawing.
+///
+/// Clearing always covers the whole target, not only the viewport. Cameras sharing a target
+/// should clear color only in the first one and load it in the rest. Clearing depth is fine for
+/// each of them, since the earlier views are done with it by then.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewClear {
+    /// `None` keeps what earlier cameras drew
+    pub color: Option<wgpu::Color>,
+    pub depth: bool,
+}
+
+impl Default for ViewClear {
+    fn default() -> Self {
+        Self {
+            color: Some(wgpu::Color::BLACK),
+            depth: true,
+        }
+    }
+}
+
+impl ViewClear {
+    /// for views drawn over others, picture-in-picture or split screen halves after the first
+    pub fn depth_only() -> Self {
+        Self {
+            color: None,
+            depth: true,
+        }
+    }
+}
+
+/// Where and when a camera renders, the scene's main camera has one as well as every `CameraView`.
+///
+/// Views are rendered in `order`, with ties keeping the main camera first and the rest in
+/// `Scene::cameras` order. Views into a render target a material samples need a lower order than
+/// the views drawing that material, overlays a higher one than what they're drawn over.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewSettings {
+    pub viewport: Viewport,
+    pub order: i32,
+    pub clear: ViewClear,
+    pub target: ViewTarget,
+}
+
+impl Default for ViewSettings {
+    fn default() -> Self {
+        Self {
+            viewport: Viewport::FULL,
+            order: 0,
+            clear: Default::default(),
+            target: ViewTarget::Window,
+        }
+    }
+}
+
+impl ViewSettings {
+    /// size of the target in pixels, `None` if it's a render target that doesn't exist
+    pub fn target_size(
+        &self,
+        window_width: u32,
+        window_height: u32,
+        targets: &RenderTargetManager,
+    ) -> Option<(u32, u32)> {
+        match self.target {
+            ViewTarget::Window => Some((window_width, window_height)),
+            ViewTarget::Texture(id) => targets
+                .find_by_id(id)
+                .map(|item| (item.render_target.width, item.render_target.height)),
+        }
+    }
+
+    /// Begins a pass into the target with the clear settings and viewport set.
+    ///
+    /// `window` is the view used for `ViewTarget::Window`, drawn through the renderer's MSAA and
+    /// depth textures like the main pass. `None` if the render target doesn't exist
+    pub fn begin_render_pass<'a>(
+        &self,
+        encoder: &'a mut wgpu::CommandEncoder,
+        renderer: &'a Renderer,
+        window: &'a wgpu::TextureView,
+        window_size: (u32, u32),
+    ) -> Option<wgpu::RenderPass<'a>> {
+        let color_load = match self.clear.color {
+            Some(color) => wgpu::LoadOp::Clear(color),
+            None => wgpu::LoadOp::Load,
+        };
+        let depth_ops = wgpu::Operations {
+            load: if self.clear.depth {
+                wgpu::LoadOp::Clear(1.0)
+            } else {
+                wgpu::LoadOp::Load
+            },
+            store: wgpu::StoreOp::Store,
+        };
+
+        let (color_attachments, depth, (width, height)) = match self.target {
+            ViewTarget::Window => {
+                let mut attachment = renderer.color_attachment(window);
+                // later views may load the multisampled target, so it has to be kept
+                attachment.ops = wgpu::Operations {
+                    load: color_load,
+                    store: wgpu::StoreOp::Store,
+                };
+                let depth = renderer.depth_texture.as_ref().map(|depth| &depth.view);
+                (vec![Some(attachment)], depth, window_size)
+            }
+            ViewTarget::Texture(id) => {
+                let target = &renderer.render_target_manager.find_by_id(id)?.render_target;
+                let attachments = target.color_attachments_with(color_load);
+                let depth = target.depth.as_ref().map(|depth| &depth.view);
+                (attachments, depth, (target.width, target.height))
+            }
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Camera View Pass"),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment: depth.map(|view| wgpu::RenderPassDepthStencilAttachment {
+                view,
+                depth_ops: Some(depth_ops),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        let [x, y, width, height] = self.viewport.pixels(width, height);
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+
+        Some(render_pass)
+    }
+}
+
+/// A camera besides the scene's main one, with where and when it renders in `settings`
+pub struct CameraView {
+    pub camera: Camera,
+    pub projection: Projection,
+    pub uniform: CameraUniform,
+    pub settings: ViewSettings,
+}
+
+impl CameraView {
+    pub fn new(camera: Camera, projection: Projection) -> Self {
+        Self {
+            camera,
+            projection,
+            uniform: CameraUniform::new(),
+            settings: Default::default(),
+        }
+    }
+
+    pub fn viewport(mut self, viewport: Viewport) -> Self {
+        self.settings.viewport = viewport;
+        self
+    }
+
+    pub fn order(mut self, order: i32) -> Self {
+        self.settings.order = order;
+        self
+    }
+
+    pub fn clear(mut self, clear: ViewClear) -> Self {
+        self.settings.clear = clear;
+        self
+    }
+
+    pub fn target(mut self, target: ViewTarget) -> Self {
+        self.settings.target = target;
+        self
+    }
+
+    pub fn update_uniform(&mut self) {
+        self.uniform
+            .update_view_proj(&self.camera, &self.projection);
+    }
+
+    /// fits the projection's aspect ratio to the viewport on a target of the given size
+    pub fn resize(&mut self, target_width: u32, target_height: u32) {
+        let [_, _, width, height] = self.settings.viewport.pixels(target_width, target_height);
+        self.projection.resize(width as u32, height as u32);
+    }
+}